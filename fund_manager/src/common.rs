@@ -75,3 +75,95 @@ define_interface! {
         ) -> Decimal;                       // coin price
     }
 }
+
+// Information about a DeFi protocol position that FundManager passes to the allocation strategy
+// component
+#[derive(ScryptoSbor, Clone, Debug)]
+pub struct PositionSnapshot {
+    pub name: String,                       // Name of the DeFi protocol position
+    pub value: Decimal,                     // USD value of the position
+    pub desired_percentage: u8,             // Desired percentage of the fund (as a share of the sum
+                                            // of all of the desired percentages)
+    pub headroom: Decimal,                  // USD value that can be added before hitting the caps
+    pub coin: ResourceAddress,              // Main coin managed by the position
+    pub other_coin: Option<ResourceAddress>, // Eventual other coin managed by the position
+}
+
+define_interface! {
+    AllocationStrategy impl [ScryptoStub, Trait, ScryptoTestStub] {
+
+        // Split a deposit among the DeFi protocol positions
+        fn deposit_split(
+            &self,
+            positions: Vec<PositionSnapshot>,   // Headroom is computed on the value after deposit
+            total_value: Decimal,               // Fund value before the deposit
+            amount: Decimal,                    // USD value to deposit
+        ) -> Vec<(
+            String,                             // Name of the DeFi protocol position
+            Decimal,                            // Share of amount to deposit in it (0-1)
+        )>;
+
+        // Choose the DeFi protocol positions to withdraw from
+        fn withdrawal_plan(
+            &self,
+            positions: Vec<PositionSnapshot>,
+            total_value: Decimal,               // Fund value before the withdrawal
+            amount: Decimal,                    // USD value to withdraw
+        ) -> Vec<(
+            String,                             // Name of the DeFi protocol position
+            Decimal,                            // USD value to withdraw from it
+        )>;
+    }
+}
+
+// Hash of the SBOR encoding of the arguments of a multisig operation; admins must authorize the
+// operation by providing this hash computed on the tuple of the arguments they agree on (the admin
+// proof excluded).
+pub fn arguments_hash<T: ScryptoEncode + ?Sized>(arguments: &T) -> Hash {
+    hash(scrypto_encode(arguments).unwrap())
+}
+
+// Leaf of the Merkle tree of a fund units distribution: hash of the SBOR encoding of the
+// distribution id, the account and its share of the distributed fund units.
+pub fn merkle_leaf(
+    distribution_id: u64,
+    account: Global<Account>,
+    share: Decimal,
+) -> Hash {
+    hash(scrypto_encode(&(distribution_id, account, share)).unwrap())
+}
+
+// Check that leaf belongs to the Merkle tree with the given root.
+// Each node is the hash of the concatenation of its two children sorted in ascending order, so the
+// proof is just the list of the sibling hashes from the leaf up to the root.
+pub fn verify_merkle_proof(
+    root: Hash,
+    leaf: Hash,
+    proof: &Vec<Hash>,
+) -> bool {
+    let mut computed = leaf;
+
+    for sibling in proof.iter() {
+        let mut data = vec![];
+        if computed.0 <= sibling.0 {
+            data.extend_from_slice(&computed.0);
+            data.extend_from_slice(&sibling.0);
+        } else {
+            data.extend_from_slice(&sibling.0);
+            data.extend_from_slice(&computed.0);
+        }
+        computed = hash(data);
+    }
+
+    computed == root
+}
+
+// Running hash of a distribution plan: starting from the zero hash, each (account, share) entry of
+// the plan is hashed, in order, together with the hash of the previous entries.
+pub fn distribution_plan_hash(
+    previous_hash: Hash,
+    account: Global<Account>,
+    share: Decimal,
+) -> Hash {
+    hash(scrypto_encode(&(previous_hash, account, share)).unwrap())
+}
@@ -18,13 +18,64 @@ static MAX_VECTOR_SIZE: usize = 50;
 // Acceptable value ratio that can be lost or gained when withdrawing
 static ACCEPTABLE_VALUE_DIFFERENCE: Decimal = dec!("0.1");
 
-// Admin badge NonFungibleData. Each one is just identified by a numeric id.
+// Default maximum age of the cached value of a DeFi protocol position before it is refreshed by
+// an operation that mints or burns fund units
+static DEFAULT_MAX_VALUE_AGE: i64 = 86400; // One day
+
+// Acceptable rounding error on the sum of the shares returned by the allocation strategy component
+static ALLOCATION_TOLERANCE: Decimal = dec!("0.000001");
+
+// Maximum annual management fee percentage
+static MAX_MANAGEMENT_FEE_PERCENTAGE: Decimal = dec!(10);
+
+// Number of seconds in a year, used to accrue the management fee
+static SECONDS_PER_YEAR: i64 = 31536000; // 365 days
+
+// Number of NAV checkpoints kept on ledger; older ones are overwritten
+static NAV_HISTORY_SIZE: u64 = 1000;
+
+// Maximum number of NAV checkpoints returned by a single nav_history call
+static NAV_HISTORY_PAGE_SIZE: usize = 200;
+
+// Default minimum interval between two NAV checkpoints
+static DEFAULT_NAV_CHECKPOINT_INTERVAL: i64 = 3600; // One hour
+
+// Acceptable rounding error on the sum of the shares of a distribution plan
+static DISTRIBUTION_SHARES_TOLERANCE: Decimal = dec!("0.000001");
+
+// Default time after which the fund units of a distribution left in the AccountLocker can be
+// reclaimed
+static DEFAULT_UNCLAIMED_FUND_UNITS_EXPIRY: i64 = 31536000; // One year
+
+// Maximum number of claim NFTs examined by finish_matured_unstakes and pending_claims
+static MAX_CLAIM_NFTS_SCAN: usize = 200;
+
+// Number of variants of AuthorizedOperation, derived from its last variant
+static NUMBER_OF_OPERATIONS: u8 = AuthorizedOperation::LAST as u8 + 1;
+
+// Admin badge NonFungibleData. Each one is identified by a numeric id and can carry the name and
+// the role of its owner.
 #[derive(ScryptoSbor, NonFungibleData)]
 struct Admin {
+    #[mutable]
+    name: String,
+    #[mutable]
+    role: String,
+}
+
+// Vesting receipt NonFungibleData. The receipt releases fund_unit_amount fund units linearly
+// between start and end; claimed_amount is the amount already claimed.
+#[derive(ScryptoSbor, NonFungibleData)]
+struct VestingReceipt {
+    fund_unit_amount: Decimal,
+    start: i64,
+    end: i64,
+    #[mutable]
+    claimed_amount: Decimal,
 }
 
 // Admin operations that require authorization from other admins.
-#[derive(ScryptoSbor, PartialEq, Debug)]
+#[derive(ScryptoSbor, PartialEq, Eq, Hash, Clone, Copy, Debug)]
 #[repr(u8)]
 pub enum AuthorizedOperation {
     WithdrawValidatorBadge      = 0,    // withdraw_validator_badge method
@@ -39,6 +90,29 @@ pub enum AuthorizedOperation {
     SetWithdrawalFee            = 9,    // set_withdrawal_fee method
     MintBotBadge                = 10,   // mint_bot_badge method
     SetBuybackFund              = 11,   // set_buyback_fund method
+    SetRebalanceMaxPercentage   = 12,   // set_rebalance_max_percentage method
+    SetEntryFee                 = 13,   // set_entry_fee method
+    SetMaxValueAge              = 14,   // set_max_value_age method
+    RevokeAdminBadge            = 15,   // revoke_admin_badge method
+    SetOperationDelay           = 16,   // set_operation_delay method
+    Unpause                     = 17,   // unpause method
+    SetCircuitBreakerPercentage = 18,   // set_circuit_breaker_percentage method
+    StartLiquidation            = 19,   // start_liquidation method
+    LiquidateDefiProtocol       = 20,   // liquidate_defi_protocol method
+    SetDefiProtocolCaps         = 21,   // set_defi_protocol_caps method
+    SetAllocationStrategy       = 22,   // set_allocation_strategy method
+    SetManagementFee            = 23,   // set_management_fee method
+    SetPerformanceFee           = 24,   // set_performance_fee method
+    SetUnclaimedFundUnitsExpiry = 25,   // set_unclaimed_fund_units_expiry method
+    SetVestingPeriod            = 26,   // set_vesting_period method
+    SetMinDepositValue          = 27,   // set_min_deposit_value method
+    SetNavCheckpointInterval    = 28,   // set_nav_checkpoint_interval method
+    AbortDistribution           = 29,   // abort_distribution method
+}
+impl AuthorizedOperation {
+    // The variant with the highest number; new operations are appended after it and become the
+    // new LAST
+    const LAST: AuthorizedOperation = AuthorizedOperation::AbortDistribution;
 }
 impl From<u8> for AuthorizedOperation {
     fn from(orig: u8) -> Self {
@@ -55,6 +129,24 @@ impl From<u8> for AuthorizedOperation {
             9  => return AuthorizedOperation::SetWithdrawalFee,
             10 => return AuthorizedOperation::MintBotBadge,
             11 => return AuthorizedOperation::SetBuybackFund,
+            12 => return AuthorizedOperation::SetRebalanceMaxPercentage,
+            13 => return AuthorizedOperation::SetEntryFee,
+            14 => return AuthorizedOperation::SetMaxValueAge,
+            15 => return AuthorizedOperation::RevokeAdminBadge,
+            16 => return AuthorizedOperation::SetOperationDelay,
+            17 => return AuthorizedOperation::Unpause,
+            18 => return AuthorizedOperation::SetCircuitBreakerPercentage,
+            19 => return AuthorizedOperation::StartLiquidation,
+            20 => return AuthorizedOperation::LiquidateDefiProtocol,
+            21 => return AuthorizedOperation::SetDefiProtocolCaps,
+            22 => return AuthorizedOperation::SetAllocationStrategy,
+            23 => return AuthorizedOperation::SetManagementFee,
+            24 => return AuthorizedOperation::SetPerformanceFee,
+            25 => return AuthorizedOperation::SetUnclaimedFundUnitsExpiry,
+            26 => return AuthorizedOperation::SetVestingPeriod,
+            27 => return AuthorizedOperation::SetMinDepositValue,
+            28 => return AuthorizedOperation::SetNavCheckpointInterval,
+            29 => return AuthorizedOperation::AbortDistribution,
             _  => Runtime::panic("Unknown operation".to_string()),
         };
     }
@@ -62,17 +154,97 @@ impl From<u8> for AuthorizedOperation {
 
 // This struct represents the authorization from one admin (allower_admin_id) to another admin
 // (allowed_admin_id) to perform an operation (authorized_operation).
-// Depending on the operation some optional information can be required (protocol_name, percentage,
-// account_address).
+// The authorization is bound to the exact arguments the operation will be invoked with through
+// the hash of their SBOR encoding (see arguments_hash).
 #[derive(ScryptoSbor, Debug)]
 struct Authorization {
     timestamp: i64,
     allower_admin_id: u8,
     allowed_admin_id: u8,
     authorized_operation: AuthorizedOperation,
-    protocol_name: Option<String>,
-    percentage: Option<u8>,
-    account_address: Option<Global<Account>>,
+    arguments_hash: Hash,
+}
+
+// This struct represents the off-ledger signed authorizations submitted through
+// authorize_with_signatures.
+// They can only be consumed by a multisig operation performed in the same transaction
+// (transaction_hash) and before deadline; they are never added to the authorization vector.
+#[derive(ScryptoSbor, Debug)]
+struct SignedAuthorization {
+    transaction_hash: Hash,
+    deadline: i64,
+    allower_admin_ids: Vec<u8>,
+    allowed_admin_id: u8,
+    authorized_operation: AuthorizedOperation,
+    arguments_hash: Hash,
+}
+
+// This struct represents an authorized multisig operation that is waiting for its delay to elapse
+// before it can be performed by the allowed admin.
+// If not performed, the operation expires AUTHORIZATION_TIMEOUT seconds after executable_from.
+// target_admin_id is the admin badge a queued revoke_admin_badge is going to revoke: its owner
+// can't cancel the operation.
+#[derive(ScryptoSbor, Debug)]
+struct QueuedOperation {
+    id: u64,
+    allowed_admin_id: u8,
+    authorized_operation: AuthorizedOperation,
+    arguments_hash: Hash,
+    executable_from: i64,
+    target_admin_id: Option<u8>,
+}
+
+// This struct is a checkpoint of the fund value; fund_unit_value is the gross value of a fund unit
+// (total_value / fund_unit_supply).
+#[derive(ScryptoSbor, Debug)]
+struct NavCheckpoint {
+    timestamp: i64,
+    total_value: Decimal,
+    fund_unit_supply: Decimal,
+    fund_unit_value: Decimal,
+}
+
+// This struct represents a pull based distribution of fund units: the bot commits the Merkle root
+// of the (account, share) snapshot and the stakers claim their share of fund_unit_amount from
+// vault with a Merkle proof.
+// After unclaimed_fund_units_expiry the bot can sweep the fund units that have not been claimed.
+#[derive(ScryptoSbor, Debug)]
+struct MerkleDistribution {
+    merkle_root: Hash,
+    fund_unit_amount: Decimal,
+    vault: FungibleVault,
+    timestamp: i64,
+    swept: bool,
+}
+
+// This struct represents the distribution plan committed by the bot before pushing the fund units
+// through fund_units_distribution: the hash of the whole plan (see distribution_plan_hash) and the
+// number of stakers, plus the running hash, the sum of the shares and the number of stakers paid
+// so far.
+#[derive(ScryptoSbor, Debug)]
+struct DistributionPlan {
+    id: u64,
+    plan_hash: Hash,
+    number_of_stakers: u64,
+    running_hash: Hash,
+    cumulative_share: Decimal,
+    paid_stakers: u64,
+}
+
+// This struct contains the fund units of each distribution plan (by id) that
+// fund_units_distribution had to leave in the AccountLocker for an account.
+#[derive(ScryptoSbor, Debug)]
+struct UnclaimedFundUnits {
+    amounts: IndexMap<u64, Decimal>,
+}
+
+// This struct contains when the fund units left in the AccountLocker by a distribution plan were
+// first found there and how many of them are still there (as far as the FundManager knows; the amount is reconciled
+// with the AccountLocker when the fund units of an account are reclaimed).
+#[derive(ScryptoSbor, Debug)]
+struct UnclaimedDistribution {
+    timestamp: i64,
+    amount: Decimal,
 }
 
 // This struct describes one of the FundManager investment in a DeFi protocol.
@@ -83,6 +255,7 @@ struct Authorization {
 #[derive(ScryptoSbor, Debug)]
 struct DefiProtocol {
     value: Decimal, // Investement value in USD
+    last_update: i64, // When value was last updated (seconds since Unix epoch)
     desired_percentage: u8, // Desired percentage of the fund to invest in this protocol
     wrapper: DefiProtocolInterfaceScryptoStub,
     coin: ResourceAddress, // Example coin: xUSDC
@@ -91,6 +264,8 @@ struct DefiProtocol {
                                                   // from the Morpher oracle
     other_coin: Option<ResourceAddress>, // Only for protocols managing two coins, i.e. providing
                                          // liquidity to a Dex
+    max_value: Option<Decimal>, // Hard cap on the USD value invested in this protocol
+    max_percentage: Option<u8>, // Hard cap on the percentage of the fund invested in this protocol
 }
 
 // This event is issued when the LSU unstake starts and a claim NFT is minted.
@@ -100,8 +275,22 @@ struct LsuUnstakeStartedEvent {
     claim_nft_id: NonFungibleLocalId,
 }
 
+// This event is issued when a user deposits LSUs in exchange for fund units.
+// The LSUs are unstaked and a claim NFT is minted; the fund units have been minted at the XRD
+// redemption value of the LSUs.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct LsuDepositEvent {
+    lsu_amount: Decimal,
+    xrd_amount: Decimal,
+    claim_nft_id: NonFungibleLocalId,
+    fund_unit_amount: Decimal,
+    total_value: Decimal,
+}
+
 // This event is issued when the LSU unstake is completed and the resulting XRD have been invested
-// in a DeFi protocol.
+// in one or more DeFi protocols.
+// defi_protocol_name and protocol_value refer to the DeFi protocol that received most of the XRD;
+// the amount of XRD invested in each DeFi protocol and its new value are reported too.
 // It also contains the amount of new fund units that must be distributed.
 #[derive(ScryptoSbor, ScryptoEvent, Debug)]
 struct LsuUnstakeCompletedEvent {
@@ -110,6 +299,8 @@ struct LsuUnstakeCompletedEvent {
     fund_units_to_distribute: Decimal,
     protocol_value: Decimal,
     total_value: Decimal,
+    defi_protocols_xrd_amount: IndexMap<String, Decimal>,
+    defi_protocols_value: IndexMap<String, Decimal>,
 }
 
 // This event is issued when a user swaps his fund units for the coins that were invested in a DeFi
@@ -144,6 +335,264 @@ struct ProtocolValueUpdateEvent {
     total_value: Decimal,
 }
 
+// This event is issued when a user buys fund units by depositing coins.
+// The coins are invested in one or more DeFi protocols; the event reports the new value of each of
+// them.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct SubscriptionEvent {
+    coin: ResourceAddress,
+    coin_amount: Decimal,
+    value: Decimal,
+    fund_unit_amount: Decimal,
+    defi_protocols_value: IndexMap<String, Decimal>,
+    total_value: Decimal,
+}
+
+// This event is emitted when the bot moves value from an overweight DeFi protocol position to an
+// underweight one.
+// It reports the values of both positions and of the whole fund before and after the operation.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct RebalanceEvent {
+    from_defi_protocol_name: String,
+    to_defi_protocol_name: String,
+    moved_value: Decimal,
+    from_protocol_value_before: Decimal,
+    from_protocol_value_after: Decimal,
+    to_protocol_value_before: Decimal,
+    to_protocol_value_after: Decimal,
+    total_value_before: Decimal,
+    total_value_after: Decimal,
+}
+
+// This event is emitted when an admin authorizes another admin to perform a multisig operation.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct AuthorizationGrantedEvent {
+    allower_admin_id: u8,
+    allowed_admin_id: u8,
+    authorized_operation: AuthorizedOperation,
+    arguments_hash: Hash,
+    expiry: i64,
+}
+
+// This event is emitted when an admin revokes one of his pending authorizations.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct AuthorizationRevokedEvent {
+    allower_admin_id: u8,
+    allowed_admin_id: u8,
+    authorized_operation: AuthorizedOperation,
+    arguments_hash: Hash,
+}
+
+// This event is emitted when an admin performs a multisig operation; it lists the admins whose
+// authorizations have been consumed.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct AuthorizationConsumedEvent {
+    allower_admin_ids: Vec<u8>,
+    allowed_admin_id: u8,
+    authorized_operation: AuthorizedOperation,
+    arguments_hash: Hash,
+}
+
+// This event is emitted when an expired authorization is removed.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct AuthorizationExpiredEvent {
+    allower_admin_id: u8,
+    allowed_admin_id: u8,
+    authorized_operation: AuthorizedOperation,
+    arguments_hash: Hash,
+}
+
+// This event is emitted when an authorized multisig operation is queued waiting for its delay to
+// elapse.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct OperationQueuedEvent {
+    queued_operation_id: u64,
+    allowed_admin_id: u8,
+    authorized_operation: AuthorizedOperation,
+    arguments_hash: Hash,
+    executable_from: i64,
+    expiry: i64,
+}
+
+// This event is emitted when a queued operation is performed.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct QueuedOperationExecutedEvent {
+    queued_operation_id: u64,
+    allowed_admin_id: u8,
+    authorized_operation: AuthorizedOperation,
+    arguments_hash: Hash,
+}
+
+// This event is emitted when an admin cancels a queued operation.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct QueuedOperationCancelledEvent {
+    queued_operation_id: u64,
+    canceller_admin_id: u8,
+    allowed_admin_id: u8,
+    authorized_operation: AuthorizedOperation,
+    arguments_hash: Hash,
+}
+
+// This event is emitted when a queued operation that has not been performed in time is removed.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct QueuedOperationExpiredEvent {
+    queued_operation_id: u64,
+    allowed_admin_id: u8,
+    authorized_operation: AuthorizedOperation,
+    arguments_hash: Hash,
+}
+
+// This event is emitted when an admin registers the public key he uses to sign off-ledger
+// authorizations.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct SigningKeyRegisteredEvent {
+    admin_id: u8,
+    public_key: Ed25519PublicKey,
+}
+
+// This event is emitted when the pause state changes.
+// admin_id is the admin who paused or unpaused; defi_protocol_name is the DeFi protocol position
+// whose value drop tripped the circuit breaker.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct PauseStateEvent {
+    withdrawals_paused: bool,
+    deposits_paused: bool,
+    bot_operations_paused: bool,
+    admin_id: Option<u8>,
+    defi_protocol_name: Option<String>,
+}
+
+// This event is emitted when the admins start the liquidation of the fund.
+// burnt_fund_units is the amount of fund units held by the component and not assigned to anyone
+// yet that have been burnt.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct LiquidationStartedEvent {
+    total_value: Decimal,
+    burnt_fund_units: Decimal,
+}
+
+// This event is emitted when coins are moved to the redemption vaults during the liquidation of
+// the fund; defi_protocol_name is None when they come from a claim NFT.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct LiquidationEvent {
+    defi_protocol_name: Option<String>,
+    coins: IndexMap<ResourceAddress, Decimal>,
+}
+
+// This event is emitted when a user burns fund units for his share of the redemption vaults.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct RedemptionEvent {
+    fund_unit_amount: Decimal,
+    coins: IndexMap<ResourceAddress, Decimal>,
+}
+
+// This event is emitted when the accrued management fee is minted and sent to the treasury
+// account.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct ManagementFeeEvent {
+    fund_unit_amount: Decimal,
+    account: ComponentAddress,
+    elapsed_seconds: i64,
+}
+
+// This event is emitted when the performance fee is crystallized: fund units are minted to the
+// treasury account for the gains of the gross fund unit value above the high-water mark.
+// high_water_mark is the new mark, that is the gross fund unit value after the fee.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct PerformanceFeeEvent {
+    fund_unit_amount: Decimal,
+    account: ComponentAddress,
+    previous_high_water_mark: Decimal,
+    fund_unit_value: Decimal,
+    high_water_mark: Decimal,
+}
+
+// This event is emitted when the bot commits the Merkle root of a fund units distribution.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct MerkleRootCommittedEvent {
+    distribution_id: u64,
+    merkle_root: Hash,
+    fund_unit_amount: Decimal,
+}
+
+// This event is emitted when the bot sweeps the unclaimed fund units of a Merkle distribution.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct MerkleDistributionSweptEvent {
+    distribution_id: u64,
+    fund_unit_amount: Decimal,
+    rolled_over: bool,
+}
+
+// This event is emitted when the fund units of a Merkle distribution leaf are claimed.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct FundUnitsClaimedEvent {
+    distribution_id: u64,
+    account: ComponentAddress,
+    share: Decimal,
+    fund_unit_amount: Decimal,
+}
+
+// This event is emitted when the bot commits a distribution plan.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct DistributionPlanCommittedEvent {
+    distribution_id: u64,
+    plan_hash: Hash,
+    number_of_stakers: u64,
+    fund_unit_amount: Decimal,
+}
+
+// This event is emitted for each batch of fund units pushed by fund_units_distribution.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct FundUnitsDistributedEvent {
+    distribution_id: u64,
+    number_of_stakers: u64,
+    fund_unit_amount: Decimal,
+    cumulative_share: Decimal,
+}
+
+// This event is emitted when a distribution plan is completed; burnt_fund_units is the rounding
+// remainder.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct FundUnitsDistributionCompletedEvent {
+    distribution_id: u64,
+    number_of_stakers: u64,
+    cumulative_share: Decimal,
+    burnt_fund_units: Decimal,
+}
+
+// This event is emitted when the admins abort the distribution in progress; fund_unit_amount is
+// the amount of fund units that were not distributed.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct DistributionAbortedEvent {
+    distribution_id: Option<u64>,
+    fund_unit_amount: Decimal,
+    rolled_over: bool,
+}
+
+// This event is emitted when the expired fund units of an account are reclaimed from the
+// AccountLocker; amounts contains the reclaimed fund units of each distribution plan.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct UnclaimedFundUnitsReclaimedEvent {
+    account: ComponentAddress,
+    amounts: IndexMap<u64, Decimal>,
+    rolled_over: bool,
+}
+
+// This event is emitted when the vested fund units of one or more vesting receipts are claimed.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct VestedFundUnitsClaimedEvent {
+    vesting_receipt_ids: Vec<NonFungibleLocalId>,
+    fund_unit_amount: Decimal,
+}
+
+// This event is emitted when an admin badge is revoked.
+#[derive(ScryptoSbor, ScryptoEvent, Debug)]
+struct AdminBadgeRevokedEvent {
+    admin_badge_id: u8,
+    number_of_admins: u8,
+    min_authorizers: IndexMap<AuthorizedOperation, u8>,
+}
+
 // This event is emitted when a protocol is removed from the fund.
 #[derive(ScryptoSbor, ScryptoEvent, Debug)]
 struct RemovedProtocolEvent {
@@ -159,10 +608,52 @@ struct RemovedProtocolEvent {
     AdminDepositEvent,
     ProtocolValueUpdateEvent,
     RemovedProtocolEvent,
+    RebalanceEvent,
+    SubscriptionEvent,
+    LsuDepositEvent,
+    AuthorizationGrantedEvent,
+    AuthorizationRevokedEvent,
+    AuthorizationConsumedEvent,
+    AuthorizationExpiredEvent,
+    AdminBadgeRevokedEvent,
+    OperationQueuedEvent,
+    QueuedOperationExecutedEvent,
+    QueuedOperationCancelledEvent,
+    QueuedOperationExpiredEvent,
+    SigningKeyRegisteredEvent,
+    PauseStateEvent,
+    LiquidationStartedEvent,
+    LiquidationEvent,
+    RedemptionEvent,
+    ManagementFeeEvent,
+    PerformanceFeeEvent,
+    MerkleRootCommittedEvent,
+    MerkleDistributionSweptEvent,
+    FundUnitsClaimedEvent,
+    DistributionPlanCommittedEvent,
+    FundUnitsDistributedEvent,
+    FundUnitsDistributionCompletedEvent,
+    DistributionAbortedEvent,
+    UnclaimedFundUnitsReclaimedEvent,
+    VestedFundUnitsClaimedEvent,
 )]
 #[types(
     String,
     DefiProtocol,
+    NonFungibleLocalId,
+    Decimal,
+    u8,
+    Ed25519PublicKey,
+    Hash,
+    i64,
+    ResourceAddress,
+    FungibleVault,
+    u64,
+    NavCheckpoint,
+    MerkleDistribution,
+    ComponentAddress,
+    UnclaimedFundUnits,
+    UnclaimedDistribution,
 )]
 mod fund_manager {
 
@@ -186,9 +677,33 @@ mod fund_manager {
             set_withdrawal_fee => PUBLIC;
             mint_bot_badge => PUBLIC;
             set_buyback_fund => PUBLIC;
+            set_rebalance_max_percentage => PUBLIC;
+            set_entry_fee => PUBLIC;
+            set_max_value_age => PUBLIC;
+            revoke_admin_badge => PUBLIC;
+            set_operation_delay => PUBLIC;
+            unpause => PUBLIC;
+            set_circuit_breaker_percentage => PUBLIC;
+            start_liquidation => PUBLIC;
+            liquidate_defi_protocol => PUBLIC;
+            set_defi_protocol_caps => PUBLIC;
+            set_allocation_strategy => PUBLIC;
+            set_management_fee => PUBLIC;
+            set_performance_fee => PUBLIC;
+            set_unclaimed_fund_units_expiry => PUBLIC;
+            set_vesting_period => PUBLIC;
+            set_min_deposit_value => PUBLIC;
+            set_nav_checkpoint_interval => PUBLIC;
+            abort_distribution => PUBLIC;
 
             // Single admin operations
             authorize_admin_operation => PUBLIC;
+            revoke_authorization => PUBLIC;
+            set_admin_badge_data => PUBLIC;
+            cancel_queued_operation => PUBLIC;
+            register_signing_key => PUBLIC;
+            authorize_with_signatures => PUBLIC;
+            pause => PUBLIC;
             deposit_validator_badge => restrict_to: [OWNER];
             deposit_coin => restrict_to: [OWNER];
             deposit_protocol_token => restrict_to: [OWNER];
@@ -201,14 +716,46 @@ mod fund_manager {
             start_unlock_owner_stake_units => restrict_to: [bot];
             start_unstake => restrict_to: [bot];
             finish_unstake => restrict_to: [bot];
+            finish_matured_unstakes => restrict_to: [bot];
+            commit_distribution_plan => restrict_to: [bot];
             fund_units_distribution => restrict_to: [bot];
+            reclaim_unclaimed_fund_units => restrict_to: [bot];
+            commit_distribution_merkle_root => restrict_to: [bot];
+            sweep_merkle_distribution => restrict_to: [bot];
             update_defi_protocols_value => restrict_to: [bot];
             set_defi_protocols_percentage => restrict_to: [bot];
+            rebalance => restrict_to: [bot];
 
             // Unauthenticated user operation
             withdraw => PUBLIC;
+            withdraw_multi => PUBLIC;
+            withdraw_with_min_output => PUBLIC;
+            subscribe => PUBLIC;
+            deposit_lsu => PUBLIC;
             fund_unit_value => PUBLIC;
             fund_details => PUBLIC;
+            defi_protocols_value_age => PUBLIC;
+            pending_authorizations => PUBLIC;
+            queued_operations => PUBLIC;
+            operation_delays => PUBLIC;
+            min_authorizers => PUBLIC;
+            pause_state => PUBLIC;
+            liquidate_claim_nft => PUBLIC;
+            redeem => PUBLIC;
+            redemption_amounts => PUBLIC;
+            defi_protocols_headroom => PUBLIC;
+            management_fee_preview => PUBLIC;
+            performance_fee_details => PUBLIC;
+            nav_history => PUBLIC;
+            nav_return => PUBLIC;
+            claim_fund_units => PUBLIC;
+            merkle_distribution => PUBLIC;
+            fund_units_claimed => PUBLIC;
+            distribution_plan => PUBLIC;
+            unclaimed_fund_units => PUBLIC;
+            claim_vested => PUBLIC;
+            vested_amount => PUBLIC;
+            pending_claims => PUBLIC;
         }
     }
 
@@ -220,14 +767,37 @@ mod fund_manager {
         // Resource manager for minting fund units
         fund_unit_resource_manager: FungibleResourceManager,
 
+        // Resource manager for minting vesting receipts, the numeric id of the last one and the
+        // vault containing the fund units they release
+        vesting_receipt_resource_manager: NonFungibleResourceManager,
+        last_vesting_receipt_id: u64,
+        vesting_vault: FungibleVault,
+
+        // Vesting period of the fund units distributed by fund_units_distribution; zero means they
+        // are distributed liquid
+        vesting_period: i64,
+
         // Where to store the Validator owner badge
         validator_badge_vault: NonFungibleVault,
 
         // List of pending admin authorized operations (limited to MAX_VECTOR_SIZE)
         authorization_vector: Vec<Authorization>,
 
-        // Minimum number of distinct admin authorizations needed for a multisig operation
-        min_authorizers: u8,
+        // Minimum number of distinct admin authorizations needed for each multisig operation.
+        // Operations not in the map (including the changes to the map itself) require the
+        // strictest threshold
+        min_authorizers: IndexMap<AuthorizedOperation, u8>,
+
+        // Delay (in seconds) between the authorization and the execution of each multisig
+        // operation; operations not in the map have no delay
+        operation_delays: IndexMap<AuthorizedOperation, i64>,
+
+        // List of authorized operations waiting for their delay to elapse (limited to
+        // MAX_VECTOR_SIZE)
+        operation_queue: Vec<QueuedOperation>,
+
+        // Numeric id of the last queued operation
+        last_queued_operation_id: u64,
 
         // List of names assigned to DeFi protocol positions (limited to MAX_VECTOR_SIZE)
         // This is needed because KeyValueStore is not iterable
@@ -246,15 +816,37 @@ mod fund_manager {
         // A Vault to store claim NFTs of the LSU being unstaked
         claim_nft_vault: NonFungibleVault,
 
+        // Claim NFTs received from deposit_lsu operations and their USD value at deposit time.
+        // Fund units for them have already been minted and their value is included in
+        // total_value
+        prepaid_claims: KeyValueStore<NonFungibleLocalId, Decimal>,
+
+        // Ids of the claim NFTs in claim_nft_vault indexed by a sequential number in the order
+        // they were received, the number of each id and the range of numbers in use.
+        // The numbers of the claim NFTs taken out of the vault are left empty until
+        // finish_matured_unstakes skips them.
+        claim_nft_ids: KeyValueStore<u64, NonFungibleLocalId>,
+        claim_nft_numbers: KeyValueStore<NonFungibleLocalId, u64>,
+        first_claim_nft_number: u64,
+        next_claim_nft_number: u64,
+
         // The AccountLocker to distribute the minted fund units
         account_locker: Global<AccountLocker>,
 
+        // The AccountLocker that only keeps what fund_units_distribution leaves for the accounts
+        // refusing the deposit, so that the unclaimed fund units can be reconciled against it
+        distribution_account_locker: Global<AccountLocker>,
+
         // The address of the component that wrappes all of the available dexes
         dex: Option<DexInterfaceScryptoStub>,
 
         // Current estimated total value of the fund
         total_value: Decimal,
 
+        // Maximum age (in seconds) of the cached value of a DeFi protocol position; older values
+        // are refreshed before minting or burning fund units
+        max_value_age: i64,
+
         // A Vault to store fund units that are being distributed.
         fund_units_vault: FungibleVault,
 
@@ -264,17 +856,120 @@ mod fund_manager {
         // The address of the component that wrappes all of the available oracles
         oracle_component: Option<OracleInterfaceScryptoStub>,
 
+        // The address of the component that decides where to deposit and withdraw from; if not
+        // set, the built-in logic is used
+        allocation_strategy: Option<AllocationStrategyInterfaceScryptoStub>,
+
         // Percentage fee for the withdraw oerations
         withdrawal_fee: u8,
 
-        // Number of minted admin badges
+        // Percentage fee for the subscribe operations
+        entry_fee: u8,
+
+        // Number of admin badges not revoked
         number_of_admins: u8,
 
+        // Numeric id of the last minted admin badge
+        last_admin_id: u8,
+
+        // Numeric ids of the revoked admin badges
+        revoked_admin_ids: Vec<u8>,
+
+        // Public keys the admins use to sign off-ledger authorizations
+        admin_signing_keys: KeyValueStore<u8, Ed25519PublicKey>,
+
+        // Digests of the off-ledger authorizations already used and their deadline; a digest is
+        // removed once its deadline has passed as the signatures can no longer be submitted
+        // (limited to MAX_VECTOR_SIZE)
+        used_authorization_digests: Vec<(Hash, i64)>,
+
+        // Off-ledger authorizations submitted in the current transaction
+        signed_authorization: Option<SignedAuthorization>,
+
         // Percentage of the unstaked XRD to send to the buyback fund
         buyback_fund_percentage: u8,
 
         // Address of the account managing the buyback fund
         buyback_fund_account: Global<Account>,
+
+        // Annual management fee percentage, accrued by time and paid by minting fund units
+        management_fee_percentage: Decimal,
+
+        // Treasury account receiving the management fee
+        management_fee_account: Option<Global<Account>>,
+
+        // When the management fee was last accrued
+        last_management_fee_accrual: i64,
+
+        // Percentage of the gains above the high-water mark paid as performance fee
+        performance_fee_percentage: u8,
+
+        // Treasury account receiving the performance fee
+        performance_fee_account: Option<Global<Account>>,
+
+        // Highest gross fund unit value on which the performance fee has been paid
+        high_water_mark: Decimal,
+
+        // Minimum number of seconds between two crystallizations of the performance fee and when
+        // the last one happened
+        crystallization_period: i64,
+        last_crystallization: i64,
+
+        // Ring buffer of the last NAV_HISTORY_SIZE checkpoints of the fund value, indexed by
+        // checkpoint number modulo NAV_HISTORY_SIZE, and the number of checkpoints ever written
+        nav_history: KeyValueStore<u64, NavCheckpoint>,
+        nav_checkpoints_count: u64,
+
+        // Minimum number of seconds between two NAV checkpoints
+        nav_checkpoint_interval: i64,
+
+        // Pull based fund units distributions by id and the id of the last one
+        merkle_distributions: KeyValueStore<u64, MerkleDistribution>,
+        last_merkle_distribution_id: u64,
+
+        // Leaves of the Merkle distributions already claimed and the id of their distribution
+        merkle_claimed_leaves: KeyValueStore<Hash, u64>,
+
+        // The distribution plan in progress and the id of the last one
+        distribution_plan: Option<DistributionPlan>,
+        last_distribution_plan_id: u64,
+
+        // Fund units that fund_units_distribution left in the AccountLocker, by account and by
+        // distribution plan, and the list of the distribution plans that left any; they are
+        // tracked when reclaim_unclaimed_fund_units finds them in the AccountLocker
+        unclaimed_fund_units: KeyValueStore<ComponentAddress, UnclaimedFundUnits>,
+        unclaimed_distributions: KeyValueStore<u64, UnclaimedDistribution>,
+        unclaimed_distribution_ids: Vec<u64>,
+
+        // Time after which the fund units left in the AccountLocker can be reclaimed
+        unclaimed_fund_units_expiry: i64,
+
+        // Reclaimed fund units to add to the next distribution
+        rolled_over_fund_units_vault: FungibleVault,
+
+        // Maximum percentage of the fund value that can be moved by a single rebalance operation
+        rebalance_max_percentage: u8,
+
+        // Minimum USD value to deposit in a DeFi protocol position when splitting a deposit
+        // among multiple positions
+        min_deposit_value: Decimal,
+
+        // Pause switches for withdrawals, deposits and bot operations
+        withdrawals_paused: bool,
+        deposits_paused: bool,
+        bot_operations_paused: bool,
+
+        // Pause everything if the value of a DeFi protocol position drops by more than this
+        // percentage in a single update (zero disables the circuit breaker)
+        circuit_breaker_percentage: u8,
+
+        // Whether the fund is being liquidated; this can't be undone
+        liquidating: bool,
+
+        // Vaults containing the coins fund unit holders can redeem once the liquidation is
+        // completed and the list of their resource addresses (KeyValueStore is not iterable)
+        redemption_vaults: KeyValueStore<ResourceAddress, FungibleVault>,
+        redemption_coins: Vec<ResourceAddress>,
     }
 
     impl FundManager {
@@ -316,7 +1011,7 @@ mod fund_manager {
             // Create the resource manager to mint admin badges (those will be minted in the init
             // method).
             // Admin badges are non fungibles identified by a number, recallable by the fund
-            // manager.
+            // manager and burnable by this component once recalled.
             let admin_badge_resource_manager = ResourceBuilder::new_integer_non_fungible::<Admin>(
                 OwnerRole::Fixed(rule!(require(fund_manager_badge_address)))
             )
@@ -339,6 +1034,14 @@ mod fund_manager {
                     recaller => rule!(require(fund_manager_badge_address));
                     recaller_updater => rule!(require(fund_manager_badge_address));
                 ))
+                .burn_roles(burn_roles!(
+                    burner => rule!(require(global_caller(component_address)));
+                    burner_updater => rule!(require(fund_manager_badge_address));
+                ))
+                .non_fungible_data_update_roles(non_fungible_data_update_roles!(
+                    non_fungible_data_updater => rule!(require(global_caller(component_address)));
+                    non_fungible_data_updater_updater => rule!(require(fund_manager_badge_address));
+                ))
                 .create_with_no_initial_supply();
             let admin_badge_address = admin_badge_resource_manager.address();
 
@@ -399,6 +1102,33 @@ mod fund_manager {
                 ))
                 .create_with_no_initial_supply();
 
+            // Create the resource manager to mint vesting receipts.
+            // Vesting receipts are non fungibles identified by a number; this component updates
+            // the amount of fund units claimed from each of them.
+            let vesting_receipt_resource_manager = ResourceBuilder::new_integer_non_fungible::<VestingReceipt>(
+                OwnerRole::Fixed(rule!(require(admin_badge_address)))
+            )
+                .metadata(metadata!(
+                    roles {
+                        metadata_setter => rule!(require(admin_badge_address));
+                        metadata_setter_updater => rule!(require(fund_manager_badge_address));
+                        metadata_locker => rule!(require(fund_manager_badge_address));
+                        metadata_locker_updater => rule!(require(fund_manager_badge_address));
+                    },
+                    init {
+                        "name" => "Fund unit vesting receipt", updatable;
+                    }
+                ))
+                .mint_roles(mint_roles!(
+                    minter => rule!(require(global_caller(component_address)));
+                    minter_updater => rule!(require(fund_manager_badge_address));
+                ))
+                .non_fungible_data_update_roles(non_fungible_data_update_roles!(
+                    non_fungible_data_updater => rule!(require(global_caller(component_address)));
+                    non_fungible_data_updater_updater => rule!(require(fund_manager_badge_address));
+                ))
+                .create_with_no_initial_supply();
+
             // Instantiate an AccountLocker
             // Both the admins and this component can use it for distribution
             let account_locker = Blueprint::<AccountLocker>::instantiate(
@@ -414,7 +1144,17 @@ mod fund_manager {
                     ])
                 ),
                 rule!(require(fund_manager_badge_address)),             // storer_updater_role
-                rule!(deny_all),                                        // recoverer_role
+                rule!(require(global_caller(component_address))),       // recoverer_role
+                rule!(require(fund_manager_badge_address)),             // recoverer_updater_role
+                None
+            );
+
+            // Instantiate the AccountLocker used by fund_units_distribution only
+            let distribution_account_locker = Blueprint::<AccountLocker>::instantiate(
+                OwnerRole::Fixed(rule!(require(admin_badge_address))),  // owner_role
+                rule!(require(global_caller(component_address))),       // storer_role
+                rule!(require(fund_manager_badge_address)),             // storer_updater_role
+                rule!(require(global_caller(component_address))),       // recoverer_role
                 rule!(require(fund_manager_badge_address)),             // recoverer_updater_role
                 None
             );
@@ -424,24 +1164,75 @@ mod fund_manager {
                 admin_badge_resource_manager: admin_badge_resource_manager,
                 bot_badge_resource_manager: bot_badge_resource_manager,
                 fund_unit_resource_manager: fund_unit_resource_manager,
+                vesting_receipt_resource_manager: vesting_receipt_resource_manager,
+                last_vesting_receipt_id: 0,
+                vesting_vault: FungibleVault::new(fund_unit_resource_manager.address()),
+                vesting_period: 0,
                 validator_badge_vault: NonFungibleVault::new(VALIDATOR_OWNER_BADGE),
                 authorization_vector: vec![],
-                min_authorizers: 0,
+                min_authorizers: IndexMap::new(),
+                operation_delays: IndexMap::new(),
+                operation_queue: vec![],
+                last_queued_operation_id: 0,
                 defi_protocols_list: vec![],
                 defi_protocols: KeyValueStore::new_with_registered_type(),
                 fund_manager_badge_vault: FungibleVault::with_bucket(fund_manager_badge_bucket),
                 validator: validator,
                 claim_nft_vault: NonFungibleVault::new(claim_nft_address),
+                prepaid_claims: KeyValueStore::new_with_registered_type(),
+                claim_nft_ids: KeyValueStore::new_with_registered_type(),
+                claim_nft_numbers: KeyValueStore::new_with_registered_type(),
+                first_claim_nft_number: 0,
+                next_claim_nft_number: 0,
                 account_locker: account_locker,
+                distribution_account_locker: distribution_account_locker,
                 dex: None,
                 total_value: Decimal::ZERO,
+                max_value_age: DEFAULT_MAX_VALUE_AGE,
                 fund_units_vault: FungibleVault::new(fund_unit_resource_manager.address()),
                 fund_units_to_distribute: Decimal::ZERO,
                 oracle_component: None,
+                allocation_strategy: None,
                 withdrawal_fee: withdrawal_fee,
+                entry_fee: 0,
                 number_of_admins: 0,
+                last_admin_id: 0,
+                revoked_admin_ids: vec![],
+                admin_signing_keys: KeyValueStore::new_with_registered_type(),
+                used_authorization_digests: vec![],
+                signed_authorization: None,
                 buyback_fund_percentage: buyback_fund_percentage,
                 buyback_fund_account: buyback_fund_account,
+                management_fee_percentage: Decimal::ZERO,
+                management_fee_account: None,
+                last_management_fee_accrual: 0,
+                performance_fee_percentage: 0,
+                performance_fee_account: None,
+                high_water_mark: Decimal::ZERO,
+                crystallization_period: 0,
+                last_crystallization: 0,
+                nav_history: KeyValueStore::new_with_registered_type(),
+                nav_checkpoints_count: 0,
+                nav_checkpoint_interval: DEFAULT_NAV_CHECKPOINT_INTERVAL,
+                merkle_distributions: KeyValueStore::new_with_registered_type(),
+                last_merkle_distribution_id: 0,
+                merkle_claimed_leaves: KeyValueStore::new_with_registered_type(),
+                distribution_plan: None,
+                last_distribution_plan_id: 0,
+                unclaimed_fund_units: KeyValueStore::new_with_registered_type(),
+                unclaimed_distributions: KeyValueStore::new_with_registered_type(),
+                unclaimed_distribution_ids: vec![],
+                unclaimed_fund_units_expiry: DEFAULT_UNCLAIMED_FUND_UNITS_EXPIRY,
+                rolled_over_fund_units_vault: FungibleVault::new(fund_unit_resource_manager.address()),
+                rebalance_max_percentage: 0,
+                min_deposit_value: Decimal::ZERO,
+                withdrawals_paused: false,
+                deposits_paused: false,
+                bot_operations_paused: false,
+                circuit_breaker_percentage: 0,
+                liquidating: false,
+                redemption_vaults: KeyValueStore::new_with_registered_type(),
+                redemption_coins: vec![],
             }
                 .instantiate()
                 .prepare_to_globalize(OwnerRole::Fixed(rule!(require(admin_badge_address))))
@@ -457,7 +1248,7 @@ mod fund_manager {
         pub fn init(
             &mut self,
             number_of_admin_badges: u8, // number of admin badges to mint
-            min_authorizers: u8, // number of authorizers for multisig operation
+            min_authorizers: u8, // initial number of authorizers for multisig operations
             fund_units_initial_supply: Decimal,
         ) -> (
             NonFungibleBucket, // Admin badges
@@ -465,7 +1256,7 @@ mod fund_manager {
         ) {
             // Make sure this method hasn't been invoked before
             assert!(
-                self.number_of_admins == 0,
+                self.last_admin_id == 0,
                 "Component already initialised",
             );
 
@@ -479,7 +1270,16 @@ mod fund_manager {
                 "The minimum number of authorizers must be smaller than the number of badges",
             );
 
-            self.min_authorizers = min_authorizers;
+            // Every operation starts with the same threshold; changes to the thresholds are not in
+            // the table as they always require the strictest one
+            for operation in 0..NUMBER_OF_OPERATIONS {
+                let authorized_operation: AuthorizedOperation = operation.into();
+
+                if authorized_operation != AuthorizedOperation::IncreaseMinAuthorizers &&
+                    authorized_operation != AuthorizedOperation::DecreaseMinAuthorizers {
+                    self.min_authorizers.insert(authorized_operation, min_authorizers);
+                }
+            }
 
             // Mint the admin badges numbering them from 1 to number_of_admin_badges
             let mut admin_badges_bucket = NonFungibleBucket::new(
@@ -489,11 +1289,15 @@ mod fund_manager {
                 admin_badges_bucket.put(
                     self.admin_badge_resource_manager.mint_non_fungible(
                         &NonFungibleLocalId::integer(n.into()),
-                        Admin {},
+                        Admin {
+                            name: "".to_string(),
+                            role: "".to_string(),
+                        },
                     )
                 );
             }
             self.number_of_admins = number_of_admin_badges;
+            self.last_admin_id = number_of_admin_badges;
 
             // Return all of the admin badges and the fund units initial supply
             (
@@ -510,13 +1314,13 @@ mod fund_manager {
             admin_proof: Proof,
             mut new_bot_account: Global<Account>,
         ) {
-            self.check_operation_authorization(
+            if !self.check_operation_authorization(
                 self.get_admin_id(admin_proof),
                 AuthorizedOperation::MintBotBadge,
-                None,
-                None,
-                Some(new_bot_account),
-            );
+                arguments_hash(&(new_bot_account,)),
+            ) {
+                return;
+            }
 
             let bot_badge = self.bot_badge_resource_manager.mint(Decimal::ONE);
 
@@ -565,13 +1369,35 @@ mod fund_manager {
                 .as_non_fungible()
                 .non_fungible::<Admin>();
 
-            u8::try_from(
+            let admin_id = u8::try_from(
                 match &non_fungible.local_id() {
                     NonFungibleLocalId::Integer(local_id) => local_id.value(),
                     _ => Runtime::panic("Incorrect proof".to_string()),
                 }
             )
-                .unwrap()
+                .unwrap();
+
+            // Make sure the badge has not been revoked
+            assert!(
+                !self.revoked_admin_ids.contains(&admin_id),
+                "Revoked admin badge",
+            );
+
+            admin_id
+        }
+
+        // An admin can invoke this method to update the name and the role stored in his own
+        // admin badge.
+        pub fn set_admin_badge_data(
+            &mut self,
+            admin_proof: Proof,
+            name: String,
+            role: String,
+        ) {
+            let admin_badge_id = NonFungibleLocalId::integer(self.get_admin_id(admin_proof).into());
+
+            self.admin_badge_resource_manager.update_non_fungible_data(&admin_badge_id, "name", name);
+            self.admin_badge_resource_manager.update_non_fungible_data(&admin_badge_id, "role", role);
         }
 
         // Private method to remove expired entries from the authorization_vector
@@ -579,7 +1405,20 @@ mod fund_manager {
             let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
 
             self.authorization_vector.retain(|authorization| {
-                authorization.timestamp + AUTHORIZATION_TIMEOUT > now
+                let valid = authorization.timestamp + AUTHORIZATION_TIMEOUT > now;
+
+                if !valid {
+                    Runtime::emit_event(
+                        AuthorizationExpiredEvent {
+                            allower_admin_id: authorization.allower_admin_id,
+                            allowed_admin_id: authorization.allowed_admin_id,
+                            authorized_operation: authorization.authorized_operation,
+                            arguments_hash: authorization.arguments_hash,
+                        }
+                    );
+                }
+
+                valid
             });
 
             // TODO: save state space by creating a new vector if len == 0 and capacity is big?
@@ -592,9 +1431,7 @@ mod fund_manager {
             admin_proof: Proof,
             allowed_admin_id: u8, // The id of badge of the admin to authorize
             authorized_operation: u8, // AuthorizedOperation
-            protocol_name: Option<String>,
-            percentage: Option<u8>,
-            account_address: Option<Global<Account>>,
+            arguments_hash: Hash, // Hash of the arguments the operation will be invoked with
         ) {
             // Verify the proof and get the id out of it
             let allower_admin_id = self.get_admin_id(admin_proof);
@@ -622,9 +1459,7 @@ mod fund_manager {
                         authorization.allower_admin_id == allower_admin_id &&
                         authorization.allowed_admin_id == allowed_admin_id &&
                         authorization.authorized_operation == authorized_operation.into() &&
-                        authorization.protocol_name == protocol_name &&
-                        authorization.percentage == percentage &&
-                        authorization.account_address == account_address
+                        authorization.arguments_hash == arguments_hash
                     })
                     .next()
                     .is_none(),
@@ -632,199 +1467,1168 @@ mod fund_manager {
             );
 
             // Add the new authorization to the authorization_vector
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
             self.authorization_vector.push(
                 Authorization {
-                    timestamp: Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch,
+                    timestamp: now,
                     allower_admin_id: allower_admin_id,
                     allowed_admin_id: allowed_admin_id,
                     authorized_operation: authorized_operation.into(),
-                    protocol_name: protocol_name,
-                    percentage: percentage,
-                    account_address: account_address,
+                    arguments_hash: arguments_hash,
                 }
             );
+
+            // Emit the AuthorizationGrantedEvent event
+            Runtime::emit_event(
+                AuthorizationGrantedEvent {
+                    allower_admin_id: allower_admin_id,
+                    allowed_admin_id: allowed_admin_id,
+                    authorized_operation: authorized_operation.into(),
+                    arguments_hash: arguments_hash,
+                    expiry: now + AUTHORIZATION_TIMEOUT,
+                }
+            );
+        }
+
+        // An admin can invoke this method to revoke his own pending authorizations.
+        // Each filter that is set restricts the authorizations to revoke; if no filter is set all
+        // of the admin's authorizations are revoked.
+        pub fn revoke_authorization(
+            &mut self,
+            admin_proof: Proof,
+            allowed_admin_id: Option<u8>, // Revoke only authorizations to this admin
+            authorized_operation: Option<u8>, // Revoke only authorizations for this operation
+            arguments_hash: Option<Hash>, // Revoke only authorizations with these arguments
+        ) {
+            // Verify the proof and get the id out of it
+            let allower_admin_id = self.get_admin_id(admin_proof);
+
+            // Remove expired entries from the authorization_vector
+            self.purge_authorization_vector();
+
+            let authorized_operation: Option<AuthorizedOperation> = authorized_operation.map(|operation| operation.into());
+
+            // Remove the matching authorizations and emit an event for each one of them
+            let authorizations_number = self.authorization_vector.len();
+            self.authorization_vector.retain(|authorization| {
+                let revoke = authorization.allower_admin_id == allower_admin_id &&
+                    allowed_admin_id.map_or(true, |id| authorization.allowed_admin_id == id) &&
+                    authorized_operation.map_or(true, |operation| authorization.authorized_operation == operation) &&
+                    arguments_hash.map_or(true, |hash| authorization.arguments_hash == hash);
+
+                if revoke {
+                    Runtime::emit_event(
+                        AuthorizationRevokedEvent {
+                            allower_admin_id: authorization.allower_admin_id,
+                            allowed_admin_id: authorization.allowed_admin_id,
+                            authorized_operation: authorization.authorized_operation,
+                            arguments_hash: authorization.arguments_hash,
+                        }
+                    );
+                }
+
+                !revoke
+            });
+
+            assert!(
+                self.authorization_vector.len() < authorizations_number,
+                "No authorization to revoke",
+            );
+        }
+
+        // An admin can invoke this method to register the Ed25519 public key he will use to sign
+        // off-ledger authorizations (see authorize_with_signatures).
+        // A previously registered key is replaced.
+        pub fn register_signing_key(
+            &mut self,
+            admin_proof: Proof,
+            public_key: Ed25519PublicKey,
+        ) {
+            // Verify the proof and get the id out of it
+            let admin_id = self.get_admin_id(admin_proof);
+
+            self.admin_signing_keys.insert(admin_id, public_key);
+
+            // Emit the SigningKeyRegisteredEvent event
+            Runtime::emit_event(
+                SigningKeyRegisteredEvent {
+                    admin_id: admin_id,
+                    public_key: public_key,
+                }
+            );
+        }
+
+        // Private method to compute the digest the admins sign off-ledger to authorize an admin
+        // to perform a multisig operation.
+        // The digest includes the component address so that signatures can't be reused on other
+        // instances of the blueprint.
+        fn authorization_digest(
+            &self,
+            allowed_admin_id: u8,
+            authorized_operation: u8,
+            arguments_hash: Hash,
+            deadline: i64,
+        ) -> Hash {
+            hash(
+                scrypto_encode(
+                    &(
+                        Runtime::global_address(),
+                        allowed_admin_id,
+                        authorized_operation,
+                        arguments_hash,
+                        deadline,
+                    )
+                ).unwrap()
+            )
+        }
+
+        // An admin can invoke this method to submit the off-ledger signatures of the other admins
+        // authorizing them to perform a multisig operation.
+        // The signatures are verified against the keys registered by the signers; if any of them
+        // is not valid the transaction fails.
+        // The signed authorizations are not added to the authorization vector: they can only be
+        // consumed by the multisig operation performed later in the same transaction and before
+        // deadline.
+        pub fn authorize_with_signatures(
+            &mut self,
+            admin_proof: Proof,
+            authorized_operation: u8, // AuthorizedOperation
+            arguments_hash: Hash, // Hash of the arguments the operation will be invoked with
+            deadline: i64, // Signatures are not valid after this time (seconds since Unix epoch)
+            signatures: Vec<(
+                u8,                 // Id of the signer admin
+                Ed25519Signature,   // Signature of the authorization digest
+            )>,
+        ) {
+            // Verify the proof and get the id out of it
+            let allowed_admin_id = self.get_admin_id(admin_proof);
+
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+            // Make sure the signatures are still valid and don't last too long
+            assert!(
+                deadline >= now,
+                "Signatures expired",
+            );
+            assert!(
+                deadline <= now + AUTHORIZATION_TIMEOUT,
+                "Deadline too far in the future",
+            );
+
+            // Make sure the signatures are not reused
+            let digest = self.authorization_digest(
+                allowed_admin_id,
+                authorized_operation,
+                arguments_hash,
+                deadline,
+            );
+            self.used_authorization_digests.retain(|(_, used_deadline)| *used_deadline >= now);
+            assert!(
+                !self.used_authorization_digests.iter().any(|(used_digest, _)| *used_digest == digest),
+                "Signatures already used",
+            );
+            assert!(
+                self.used_authorization_digests.len() < MAX_VECTOR_SIZE,
+                "Too many signed authorizations",
+            );
+            self.used_authorization_digests.push((digest, deadline));
+
+            let mut signer_admin_ids: Vec<u8> = vec![];
+            for (signer_admin_id, signature) in signatures.iter() {
+
+                // Make sure the admin isn't cheating
+                assert!(
+                    *signer_admin_id != allowed_admin_id,
+                    "You can't authorize yourself",
+                );
+                assert!(
+                    !signer_admin_ids.contains(signer_admin_id),
+                    "Duplicate signer",
+                );
+                assert!(
+                    !self.revoked_admin_ids.contains(signer_admin_id),
+                    "Revoked admin badge",
+                );
+                signer_admin_ids.push(*signer_admin_id);
+
+                // Verify the signature
+                let public_key = *self.admin_signing_keys.get(signer_admin_id)
+                    .expect("Signing key not registered");
+                assert!(
+                    CryptoUtils::ed25519_verify(&digest.0, &public_key, signature),
+                    "Invalid signature",
+                );
+
+                // Emit the AuthorizationGrantedEvent event
+                Runtime::emit_event(
+                    AuthorizationGrantedEvent {
+                        allower_admin_id: *signer_admin_id,
+                        allowed_admin_id: allowed_admin_id,
+                        authorized_operation: authorized_operation.into(),
+                        arguments_hash: arguments_hash,
+                        expiry: deadline,
+                    }
+                );
+            }
+
+            // Keep the signed authorizations for the operation performed in this transaction;
+            // any previous signed authorization was bound to another transaction and is dropped
+            self.signed_authorization = Some(
+                SignedAuthorization {
+                    transaction_hash: Runtime::transaction_hash(),
+                    deadline: deadline,
+                    allower_admin_ids: signer_admin_ids,
+                    allowed_admin_id: allowed_admin_id,
+                    authorized_operation: authorized_operation.into(),
+                    arguments_hash: arguments_hash,
+                }
+            );
+        }
+
+        // This method returns the list of pending (not expired) authorizations
+        pub fn pending_authorizations(&self) -> Vec<(
+            u8,                     // Allower admin id
+            u8,                     // Allowed admin id
+            AuthorizedOperation,    // Authorized operation
+            Hash,                   // Hash of the operation arguments
+            i64,                    // Expiry (seconds since Unix epoch)
+        )> {
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+            self.authorization_vector
+                .iter()
+                .filter(|authorization| authorization.timestamp + AUTHORIZATION_TIMEOUT > now)
+                .map(|authorization| (
+                    authorization.allower_admin_id,
+                    authorization.allowed_admin_id,
+                    authorization.authorized_operation,
+                    authorization.arguments_hash,
+                    authorization.timestamp + AUTHORIZATION_TIMEOUT,
+                ))
+                .collect()
         }
 
-        // Private method to verify that a multisig operation has been authorized
+        // Private method to verify that a multisig operation has been authorized.
+        // If the operation has a delay, the authorizations are consumed and the operation is
+        // queued; in this case false is returned and the operation must not be performed now.
+        // Once the delay is elapsed, the allowed admin can invoke the method again with the same
+        // arguments: true is returned and the operation can be performed.
         fn check_operation_authorization(
             &mut self,
             admin_id: u8,
             authorized_operation: AuthorizedOperation,
-            protocol_name: Option<String>,
-            percentage: Option<u8>,
-            account_address: Option<Global<Account>>,
-        ) {
+            arguments_hash: Hash,
+        ) -> bool {
+            let delay = self.get_operation_delay(authorized_operation);
+
+            self.check_delayed_operation_authorization(
+                admin_id,
+                authorized_operation,
+                arguments_hash,
+                delay,
+            )
+        }
+
+        // Private method to get the number of authorizations needed for a multisig operation.
+        // Operations not in the min_authorizers table require the strictest threshold.
+        fn get_min_authorizers(
+            &self,
+            authorized_operation: AuthorizedOperation,
+        ) -> u8 {
+            match self.min_authorizers.get(&authorized_operation) {
+                Some(min_authorizers) => *min_authorizers,
+                None => *self.min_authorizers.values().max().unwrap_or(&0),
+            }
+        }
+
+        // This method returns the number of authorizations needed for each multisig operation;
+        // increase_min_authorizers and decrease_min_authorizers always need the highest of them
+        pub fn min_authorizers(&self) -> IndexMap<AuthorizedOperation, u8> {
+            self.min_authorizers.clone()
+        }
+
+        // Private method to get the delay (in seconds) of a multisig operation
+        fn get_operation_delay(
+            &self,
+            authorized_operation: AuthorizedOperation,
+        ) -> i64 {
+            *self.operation_delays.get(&authorized_operation).unwrap_or(&0)
+        }
+
+        // Private method to remove expired entries from the operation_queue
+        fn purge_operation_queue(&mut self) {
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+            self.operation_queue.retain(|queued_operation| {
+                let valid = queued_operation.executable_from + AUTHORIZATION_TIMEOUT > now;
+
+                if !valid {
+                    Runtime::emit_event(
+                        QueuedOperationExpiredEvent {
+                            queued_operation_id: queued_operation.id,
+                            allowed_admin_id: queued_operation.allowed_admin_id,
+                            authorized_operation: queued_operation.authorized_operation,
+                            arguments_hash: queued_operation.arguments_hash,
+                        }
+                    );
+                }
+
+                valid
+            });
+        }
+
+        // Private method that does the work of check_operation_authorization with the specified
+        // delay
+        fn check_delayed_operation_authorization(
+            &mut self,
+            admin_id: u8,
+            authorized_operation: AuthorizedOperation,
+            arguments_hash: Hash,
+            delay: i64,
+        ) -> bool {
+            // Remove expired entries from the operation_queue
+            self.purge_operation_queue();
+
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+            // If the operation has already been queued, it can be performed once its delay is
+            // elapsed
+            let queue_position = self.operation_queue
+                .iter()
+                .position(|queued_operation| {
+                    queued_operation.allowed_admin_id == admin_id &&
+                    queued_operation.authorized_operation == authorized_operation &&
+                    queued_operation.arguments_hash == arguments_hash
+                });
+            if queue_position.is_some() {
+                let queued_operation = self.operation_queue.remove(queue_position.unwrap());
+
+                assert!(
+                    queued_operation.executable_from <= now,
+                    "Operation delay not elapsed yet",
+                );
+
+                // Emit the QueuedOperationExecutedEvent event
+                Runtime::emit_event(
+                    QueuedOperationExecutedEvent {
+                        queued_operation_id: queued_operation.id,
+                        allowed_admin_id: admin_id,
+                        authorized_operation: authorized_operation,
+                        arguments_hash: arguments_hash,
+                    }
+                );
+
+                return true;
+            }
+
             // Remove expired entries from the authorization_vector
             self.purge_authorization_vector();
 
-            // Count the received authorizations for this operation
-            let authorizers_number = self.authorization_vector
+            // Find the received authorizations for this operation
+            let mut allower_admin_ids: Vec<u8> = self.authorization_vector
                 .iter()
                 .filter(|&authorization| {
                     authorization.allowed_admin_id == admin_id &&
                     authorization.authorized_operation == authorized_operation &&
-                    authorization.protocol_name == protocol_name &&
-                    authorization.percentage == percentage &&
-                    authorization.account_address == account_address
+                    authorization.arguments_hash == arguments_hash
                 })
-                .count();
+                .map(|authorization| authorization.allower_admin_id)
+                .collect();
+
+            // Add the off-ledger authorizations submitted in this same transaction, if they are
+            // for this operation and not expired; they are consumed anyway
+            let signed_authorization = self.signed_authorization.take();
+            if signed_authorization.is_some() {
+                let signed_authorization = signed_authorization.unwrap();
+
+                if signed_authorization.transaction_hash == Runtime::transaction_hash() &&
+                    signed_authorization.deadline >= now &&
+                    signed_authorization.allowed_admin_id == admin_id &&
+                    signed_authorization.authorized_operation == authorized_operation &&
+                    signed_authorization.arguments_hash == arguments_hash {
+                    for allower_admin_id in signed_authorization.allower_admin_ids.into_iter() {
+                        if !allower_admin_ids.contains(&allower_admin_id) &&
+                            !self.revoked_admin_ids.contains(&allower_admin_id) {
+                            allower_admin_ids.push(allower_admin_id);
+                        }
+                    }
+                }
+            }
 
             // Make sure there are enough authorizers
             assert!(
-                authorizers_number >= self.min_authorizers.into(),
+                allower_admin_ids.len() >= self.get_min_authorizers(authorized_operation).into(),
                 "Operation not authorized",
             );
 
+            // Emit the AuthorizationConsumedEvent event
+            Runtime::emit_event(
+                AuthorizationConsumedEvent {
+                    allower_admin_ids: allower_admin_ids,
+                    allowed_admin_id: admin_id,
+                    authorized_operation: authorized_operation,
+                    arguments_hash: arguments_hash,
+                }
+            );
+
             // Remove authorizations for this operation
             self.authorization_vector.retain(|authorization| {
                 authorization.allowed_admin_id != admin_id ||
                 authorization.authorized_operation != authorized_operation ||
-                authorization.protocol_name != protocol_name ||
-                authorization.account_address != account_address
+                authorization.arguments_hash != arguments_hash
             });
-        }
 
-        // Get the net and gross (withdrawal fee included) USD value of a fund unit
-        pub fn fund_unit_value(&self) -> (Decimal, Decimal) {
-            // TODO: return a default value if total_value or the fund units supply is zero!
+            if delay == 0 {
+                return true;
+            }
 
-            let gross_value = self.total_value / self.fund_unit_resource_manager.total_supply().unwrap();
+            // Avoid state explosion
+            assert!(
+                self.operation_queue.len() < MAX_VECTOR_SIZE,
+                "Operation queue is getting too big",
+            );
 
-            (
-                (gross_value * (100 - self.withdrawal_fee)) / 100, // net value
-                gross_value
-            )
-        }
+            // Queue the operation
+            self.last_queued_operation_id += 1;
+            self.operation_queue.push(
+                QueuedOperation {
+                    id: self.last_queued_operation_id,
+                    allowed_admin_id: admin_id,
+                    authorized_operation: authorized_operation,
+                    arguments_hash: arguments_hash,
+                    executable_from: now + delay,
+                    target_admin_id: None,
+                }
+            );
 
-        // This method returns the list of DeFi protocol positions and their value
-        pub fn fund_details(&self) -> HashMap<String, Decimal> {
-            let mut protocols_value = HashMap::new();
+            // Emit the OperationQueuedEvent event
+            Runtime::emit_event(
+                OperationQueuedEvent {
+                    queued_operation_id: self.last_queued_operation_id,
+                    allowed_admin_id: admin_id,
+                    authorized_operation: authorized_operation,
+                    arguments_hash: arguments_hash,
+                    executable_from: now + delay,
+                    expiry: now + delay + AUTHORIZATION_TIMEOUT,
+                }
+            );
 
-            for name in self.defi_protocols_list.iter() {
-                let defi_protocol = self.defi_protocols.get(&name).unwrap();
+            false
+        }
 
-                protocols_value.insert(name.clone(), defi_protocol.value);
-            }
+        // This method returns the list of queued operations that have not expired yet
+        pub fn queued_operations(&self) -> Vec<(
+            u64,                    // Queued operation id
+            u8,                     // Allowed admin id
+            AuthorizedOperation,    // Authorized operation
+            Hash,                   // Hash of the operation arguments
+            i64,                    // Executable from (seconds since Unix epoch)
+            i64,                    // Expiry (seconds since Unix epoch)
+        )> {
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
 
-            protocols_value
+            self.operation_queue
+                .iter()
+                .filter(|queued_operation| queued_operation.executable_from + AUTHORIZATION_TIMEOUT > now)
+                .map(|queued_operation| (
+                    queued_operation.id,
+                    queued_operation.allowed_admin_id,
+                    queued_operation.authorized_operation,
+                    queued_operation.arguments_hash,
+                    queued_operation.executable_from,
+                    queued_operation.executable_from + AUTHORIZATION_TIMEOUT,
+                ))
+                .collect()
         }
 
-        // An admin can call this method to withdraw the Validator owner badge.
-        // This operation must be authorized by other admins.
-        pub fn withdraw_validator_badge(
+        // This method returns the delay (in seconds) of the multisig operations that have one
+        pub fn operation_delays(&self) -> IndexMap<AuthorizedOperation, i64> {
+            self.operation_delays.clone()
+        }
+
+        // Any admin can invoke this method to cancel a queued operation before it is performed,
+        // except the revocation of their own badge
+        pub fn cancel_queued_operation(
             &mut self,
             admin_proof: Proof,
-        ) -> NonFungibleBucket {
+            queued_operation_id: u64,
+        ) {
+            // Verify the proof and get the id out of it
+            let canceller_admin_id = self.get_admin_id(admin_proof);
 
-            // Check the admin proof and that there are enough authorizations for this operation
-            self.check_operation_authorization(
-                self.get_admin_id(admin_proof),
-                AuthorizedOperation::WithdrawValidatorBadge,
-                None,
-                None,
-                None,
+            // Remove expired entries from the operation_queue
+            self.purge_operation_queue();
+
+            let queue_position = self.operation_queue
+                .iter()
+                .position(|queued_operation| queued_operation.id == queued_operation_id)
+                .expect("Queued operation not found");
+            assert!(
+                self.operation_queue[queue_position].target_admin_id != Some(canceller_admin_id),
+                "You can't cancel the revocation of your badge",
             );
+            let queued_operation = self.operation_queue.remove(queue_position);
 
-            self.validator_badge_vault.take_all()
+            // Emit the QueuedOperationCancelledEvent event
+            Runtime::emit_event(
+                QueuedOperationCancelledEvent {
+                    queued_operation_id: queued_operation.id,
+                    canceller_admin_id: canceller_admin_id,
+                    allowed_admin_id: queued_operation.allowed_admin_id,
+                    authorized_operation: queued_operation.authorized_operation,
+                    arguments_hash: queued_operation.arguments_hash,
+                }
+            );
         }
 
-        // An admin can call this method to withdraw the fund manager badge.
-        // This operation must be authorized by other admins.
-        // Warning: the admin who performs this operation will be God!
-        pub fn withdraw_fund_manager_badge(
+        // Set the delay, in hours, between the authorization and the execution of a multisig
+        // operation; during the delay any admin can cancel the operation and fund unit holders
+        // can exit the fund before the change happens.
+        // This operation is itself delayed by the longest between its own delay and the current
+        // delay of the target operation so that delays can't be bypassed.
+        // Admins must agree on the operation and the number of hours when performing this
+        // operation.
+        pub fn set_operation_delay(
             &mut self,
             admin_proof: Proof,
-        ) -> FungibleBucket {
+            operation: u8, // AuthorizedOperation
+            hours: u32,
+        ) {
+            let authorized_operation: AuthorizedOperation = operation.into();
+            let delay = self.get_operation_delay(AuthorizedOperation::SetOperationDelay)
+                .max(self.get_operation_delay(authorized_operation));
 
-            // Check the admin proof and that there are enough authorizations for this operation
-            self.check_operation_authorization(
+            // Check admins' authorization
+            if !self.check_delayed_operation_authorization(
                 self.get_admin_id(admin_proof),
-                AuthorizedOperation::WithdrawFundManagerBadge,
-                None,
-                None,
-                None,
-            );
+                AuthorizedOperation::SetOperationDelay,
+                arguments_hash(&(operation, hours)),
+                delay,
+            ) {
+                return;
+            }
 
-            self.fund_manager_badge_vault.take_all()
+            // Update the delay
+            if hours == 0 {
+                self.operation_delays.swap_remove(&authorized_operation);
+            } else {
+                self.operation_delays.insert(authorized_operation, i64::from(hours) * 3600);
+            }
         }
 
-        // Increase by 1 the number of authorizations needed for a multisig operation (the increase
-        // does not apply to this operation).
-        // This operation must be authorized by other admins.
-        pub fn increase_min_authorizers(
-            &mut self,
-            admin_proof: Proof,
-        ) {
+        // Get the net and gross (withdrawal fee included) USD value of a fund unit.
+        // The management fee accrued and not minted yet is taken into account.
+        // When no fund unit exists a fund unit is worth one USD, so the first deposit is minted
+        // 1:1 by value.
+        pub fn fund_unit_value(&self) -> (Decimal, Decimal) {
+            let fund_unit_supply = self.fund_unit_resource_manager.total_supply().unwrap() +
+                self.management_fee_preview();
 
-            // Check the admin proof and that there are enough authorizations for this operation
-            self.check_operation_authorization(
-                self.get_admin_id(admin_proof),
-                AuthorizedOperation::IncreaseMinAuthorizers,
-                None,
-                None,
-                None,
-            );
+            let gross_value = match fund_unit_supply == Decimal::ZERO {
+                true => Decimal::ONE,
+                false => self.total_value / fund_unit_supply,
+            };
 
-            self.min_authorizers += 1;
+            (
+                (gross_value * (100 - self.withdrawal_fee)) / 100, // net value
+                gross_value
+            )
+        }
 
-            // Verify that enough admins exist or the multisig operations will be impossible!
+        // Private method to get the gross value of a fund unit before minting new ones.
+        // If the existing fund units are worth nothing no amount of new fund units would be fair,
+        // so minting is refused.
+        fn fund_unit_mint_value(&self) -> Decimal {
+            let (_, fund_unit_gross_value) = self.fund_unit_value();
             assert!(
-                self.min_authorizers < self.number_of_admins,
-                "The minimum number of authorizers must be smaller than the number of badges",
+                fund_unit_gross_value > Decimal::ZERO,
+                "Fund has no value",
             );
+
+            fund_unit_gross_value
         }
 
-        // Decrease by 1 the number of authorizations needed for a multisig operation (the decrease
-        // does not apply to this operation).
-        // This operation must be authorized by other admins.
-        // Warning: if min_authorizers goes to zero, the authorization system will be no longer
-        // effective.
-        pub fn decrease_min_authorizers(
-            &mut self,
-            admin_proof: Proof,
-        ) {
+        // Returns the amount of fund units that would be minted now as management fee for the time
+        // elapsed since the last accrual.
+        // The fee fraction f of the fund value is taken by minting supply * f / (1 - f) fund units,
+        // so that they are worth exactly f of the fund after the mint; at most one year of fee is
+        // accrued at once.
+        pub fn management_fee_preview(&self) -> Decimal {
+            let total_supply = self.fund_unit_resource_manager.total_supply().unwrap();
+            if self.management_fee_account.is_none() ||
+                self.management_fee_percentage == Decimal::ZERO ||
+                total_supply == Decimal::ZERO ||
+                self.liquidating {
+                return Decimal::ZERO;
+            }
 
-            // Check the admin proof and that there are enough authorizations for this operation
-            self.check_operation_authorization(
-                self.get_admin_id(admin_proof),
-                AuthorizedOperation::DecreaseMinAuthorizers,
-                None,
-                None,
-                None,
-            );
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+            let elapsed_seconds = now - self.last_management_fee_accrual;
+            if elapsed_seconds <= 0 {
+                return Decimal::ZERO;
+            }
+
+            let fee_fraction = (self.management_fee_percentage * elapsed_seconds.min(SECONDS_PER_YEAR)) /
+                100 / SECONDS_PER_YEAR;
 
-            self.min_authorizers -= 1;
+            total_supply * fee_fraction / (1 - fee_fraction)
         }
 
-        // Mint a new admin badge and send it to the specified account.
-        // Admins must authorize this operation ad agree on the account address to send the badge
-        // to.
-        pub fn mint_admin_badge(
+        // Private method to mint fee fund units and send them to a treasury account.
+        // The AccountLocker is used so that a treasury account refusing deposits can't block the
+        // fund.
+        fn pay_to_treasury(
             &mut self,
-            admin_proof: Proof,
-            mut new_admin_account: Global<Account>,
+            account: Global<Account>,
+            fund_unit_amount: Decimal,
         ) {
-
-            // Check the admin proof and that there are enough authorizations for this operation
-            self.check_operation_authorization(
-                self.get_admin_id(admin_proof),
-                AuthorizedOperation::MintAdminBadge,
-                None,
-                None,
-                Some(new_admin_account),
+            self.account_locker.store(
+                account,
+                self.fund_unit_resource_manager.mint(fund_unit_amount).into(),
+                true,
             );
+        }
 
-            // Mint the new admin badge
-            self.number_of_admins += 1;
-            let admin_badge = self.admin_badge_resource_manager.mint_non_fungible(
-                &NonFungibleLocalId::integer(self.number_of_admins.into()),
-                Admin {},
-            );
+        // Private method to mint the accrued management fee and send it to the treasury account.
+        // It is invoked by the user and bot operations that touch the fund before the value of a
+        // fund unit is used.
+        fn accrue_management_fee(&mut self) {
+            let fund_unit_amount = self.management_fee_preview();
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+            let elapsed_seconds = now - self.last_management_fee_accrual;
+            self.last_management_fee_accrual = now;
 
-            // and send it to the specified account
-            new_admin_account.try_deposit_or_abort(
-                admin_badge.into(),
-                None
+            if fund_unit_amount == Decimal::ZERO {
+                return;
+            }
+
+            let account = self.management_fee_account.unwrap();
+            self.pay_to_treasury(account, fund_unit_amount);
+
+            // Emit the ManagementFeeEvent event
+            Runtime::emit_event(
+                ManagementFeeEvent {
+                    fund_unit_amount: fund_unit_amount,
+                    account: account.address(),
+                    elapsed_seconds: elapsed_seconds,
+                }
+            );
+        }
+
+        // Private method to pay the performance fee if the crystallization period has elapsed and
+        // the gross fund unit value is above the high-water mark.
+        // The fee is paid by minting fund units so that their value is the fee percentage of the
+        // gains; the high-water mark is then set to the gross fund unit value after the fee, so the
+        // same gains are never charged twice.
+        fn crystallize_performance_fee(&mut self) {
+            if self.performance_fee_account.is_none() ||
+                self.performance_fee_percentage == 0 ||
+                self.liquidating ||
+                self.fund_unit_resource_manager.total_supply().unwrap() == Decimal::ZERO {
+                return;
+            }
+
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+            if now < self.last_crystallization + self.crystallization_period {
+                return;
+            }
+            self.last_crystallization = now;
+
+            // The management fee accrued so far must not be counted as a gain
+            self.accrue_management_fee();
+            let (_, fund_unit_gross_value) = self.fund_unit_value();
+
+            // The first time just set the high-water mark
+            if self.high_water_mark == Decimal::ZERO {
+                self.high_water_mark = fund_unit_gross_value;
+                return;
+            }
+
+            if fund_unit_gross_value <= self.high_water_mark {
+                return;
+            }
+
+            // Compute the USD value of the fee and the amount of fund units worth that value after
+            // minting them
+            let total_supply = self.fund_unit_resource_manager.total_supply().unwrap();
+            let fee_value = (
+                (fund_unit_gross_value - self.high_water_mark) * total_supply * self.performance_fee_percentage
+            ) / 100;
+            let fund_unit_amount = fee_value * total_supply / (self.total_value - fee_value);
+
+            let account = self.performance_fee_account.unwrap();
+            self.pay_to_treasury(account, fund_unit_amount);
+
+            let previous_high_water_mark = self.high_water_mark;
+            let (_, high_water_mark) = self.fund_unit_value();
+            self.high_water_mark = high_water_mark;
+
+            // Emit the PerformanceFeeEvent event
+            Runtime::emit_event(
+                PerformanceFeeEvent {
+                    fund_unit_amount: fund_unit_amount,
+                    account: account.address(),
+                    previous_high_water_mark: previous_high_water_mark,
+                    fund_unit_value: fund_unit_gross_value,
+                    high_water_mark: self.high_water_mark,
+                }
+            );
+        }
+
+        // Returns the performance fee percentage, the high-water mark, the crystallization period
+        // in seconds and the time of the last crystallization
+        pub fn performance_fee_details(&self) -> (u8, Decimal, i64, i64) {
+            (
+                self.performance_fee_percentage,
+                self.high_water_mark,
+                self.crystallization_period,
+                self.last_crystallization,
+            )
+        }
+
+        // Private method to write a NAV checkpoint after an operation that changed the fund value or
+        // the fund unit supply; nothing is written if the last checkpoint is more recent than
+        // nav_checkpoint_interval
+        fn record_nav_checkpoint(&mut self) {
+            let fund_unit_supply = self.fund_unit_resource_manager.total_supply().unwrap();
+            if fund_unit_supply == Decimal::ZERO {
+                return;
+            }
+
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+            if self.nav_checkpoints_count > 0 {
+                let last_timestamp = self.nav_history.get(
+                    &((self.nav_checkpoints_count - 1) % NAV_HISTORY_SIZE)
+                ).unwrap().timestamp;
+
+                if now < last_timestamp + self.nav_checkpoint_interval {
+                    return;
+                }
+            }
+
+            let (_, fund_unit_gross_value) = self.fund_unit_value();
+            self.nav_history.insert(
+                self.nav_checkpoints_count % NAV_HISTORY_SIZE,
+                NavCheckpoint {
+                    timestamp: now,
+                    total_value: self.total_value,
+                    fund_unit_supply: fund_unit_supply,
+                    fund_unit_value: fund_unit_gross_value,
+                }
+            );
+            self.nav_checkpoints_count += 1;
+        }
+
+        // Private method to find the number of the last NAV checkpoint not after the given
+        // timestamp among the ones still on ledger
+        fn nav_checkpoint_number(
+            &self,
+            timestamp: i64,
+        ) -> Option<u64> {
+            let oldest = self.nav_checkpoints_count.saturating_sub(NAV_HISTORY_SIZE);
+            if self.nav_checkpoints_count == 0 ||
+                self.nav_history.get(&(oldest % NAV_HISTORY_SIZE)).unwrap().timestamp > timestamp {
+                return None;
+            }
+
+            // Checkpoints are sorted by timestamp; binary search the last one not after timestamp
+            let mut low = oldest;
+            let mut high = self.nav_checkpoints_count - 1;
+            while low < high {
+                let middle = (low + high + 1) / 2;
+                if self.nav_history.get(&(middle % NAV_HISTORY_SIZE)).unwrap().timestamp <= timestamp {
+                    low = middle;
+                } else {
+                    high = middle - 1;
+                }
+            }
+
+            Some(low)
+        }
+
+        // Returns the NAV checkpoints written between the from and to timestamps (both included),
+        // oldest first.
+        // At most NAV_HISTORY_PAGE_SIZE checkpoints are returned; call the method again with
+        // start_number set to the last returned checkpoint number plus one to get the following
+        // ones.
+        pub fn nav_history(
+            &self,
+            from: i64,  // Seconds since Unix epoch
+            to: i64,    // Seconds since Unix epoch
+            start_number: Option<u64>, // Number of the first checkpoint to return, if known
+        ) -> Vec<(
+            u64,        // Checkpoint number
+            i64,        // Timestamp (seconds since Unix epoch)
+            Decimal,    // Fund total USD value
+            Decimal,    // Fund unit supply
+            Decimal,    // Gross USD value of a fund unit
+        )> {
+            let mut checkpoints = vec![];
+
+            let last = match self.nav_checkpoint_number(to) {
+                None => return checkpoints,
+                Some(last) => last,
+            };
+            let mut first = match self.nav_checkpoint_number(from - 1) {
+                None => self.nav_checkpoints_count.saturating_sub(NAV_HISTORY_SIZE),
+                Some(number) => number + 1,
+            };
+            if start_number.is_some() {
+                first = first.max(start_number.unwrap());
+            }
+
+            for number in first..=last {
+                if checkpoints.len() == NAV_HISTORY_PAGE_SIZE {
+                    break;
+                }
+
+                let checkpoint = self.nav_history.get(&(number % NAV_HISTORY_SIZE)).unwrap();
+                checkpoints.push((
+                    number,
+                    checkpoint.timestamp,
+                    checkpoint.total_value,
+                    checkpoint.fund_unit_supply,
+                    checkpoint.fund_unit_value,
+                ));
+            }
+
+            checkpoints
+        }
+
+        // Returns the percentage change of the gross fund unit value between two timestamps; for
+        // each timestamp the last NAV checkpoint not after it is used
+        pub fn nav_return(
+            &self,
+            from: i64,  // Seconds since Unix epoch
+            to: i64,    // Seconds since Unix epoch
+        ) -> Decimal {
+            assert!(
+                from <= to,
+                "Wrong interval",
+            );
+
+            let from_value = self.nav_history.get(
+                &(self.nav_checkpoint_number(from).expect("No checkpoint") % NAV_HISTORY_SIZE)
+            ).unwrap().fund_unit_value;
+            let to_value = self.nav_history.get(
+                &(self.nav_checkpoint_number(to).expect("No checkpoint") % NAV_HISTORY_SIZE)
+            ).unwrap().fund_unit_value;
+
+            (to_value - from_value) * 100 / from_value
+        }
+
+        // This method returns the list of DeFi protocol positions and their value
+        pub fn fund_details(&self) -> HashMap<String, Decimal> {
+            let mut protocols_value = HashMap::new();
+
+            for name in self.defi_protocols_list.iter() {
+                let defi_protocol = self.defi_protocols.get(&name).unwrap();
+
+                protocols_value.insert(name.clone(), defi_protocol.value);
+            }
+
+            protocols_value
+        }
+
+        // This method returns the list of DeFi protocol positions, their value and the age of the
+        // value
+        pub fn defi_protocols_value_age(&self) -> HashMap<
+            String,     // DeFi protocol position name
+            (
+                Decimal,    // USD value
+                i64,        // Seconds since the last value update
+            )
+        > {
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+            let mut protocols_value = HashMap::new();
+
+            for name in self.defi_protocols_list.iter() {
+                let defi_protocol = self.defi_protocols.get(&name).unwrap();
+
+                protocols_value.insert(
+                    name.clone(),
+                    (defi_protocol.value, now - defi_protocol.last_update)
+                );
+            }
+
+            protocols_value
+        }
+
+        // An admin can call this method to withdraw the Validator owner badge.
+        // This operation must be authorized by other admins.
+        pub fn withdraw_validator_badge(
+            &mut self,
+            admin_proof: Proof,
+        ) -> Option<NonFungibleBucket> {
+
+            // Check the admin proof and that there are enough authorizations for this operation
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::WithdrawValidatorBadge,
+                arguments_hash(&()),
+            ) {
+                return None;
+            }
+
+            Some(self.validator_badge_vault.take_all())
+        }
+
+        // An admin can call this method to withdraw the fund manager badge.
+        // This operation must be authorized by other admins.
+        // Warning: the admin who performs this operation will be God!
+        pub fn withdraw_fund_manager_badge(
+            &mut self,
+            admin_proof: Proof,
+        ) -> Option<FungibleBucket> {
+
+            // Check the admin proof and that there are enough authorizations for this operation
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::WithdrawFundManagerBadge,
+                arguments_hash(&()),
+            ) {
+                return None;
+            }
+
+            Some(self.fund_manager_badge_vault.take_all())
+        }
+
+        // Private method to check that the threshold of an operation can be changed
+        fn check_min_authorizers_operation(
+            operation: AuthorizedOperation,
+        ) {
+            assert!(
+                operation != AuthorizedOperation::IncreaseMinAuthorizers &&
+                operation != AuthorizedOperation::DecreaseMinAuthorizers,
+                "Changes to the thresholds always require the strictest one",
+            );
+        }
+
+        // Increase by 1 the number of authorizations needed for the specified multisig operation
+        // (the increase does not apply to this operation).
+        // This operation must be authorized by other admins and requires the strictest threshold;
+        // admins must agree on the operation whose threshold is increased.
+        pub fn increase_min_authorizers(
+            &mut self,
+            admin_proof: Proof,
+            operation: u8, // AuthorizedOperation
+        ) {
+            let authorized_operation: AuthorizedOperation = operation.into();
+            Self::check_min_authorizers_operation(authorized_operation);
+
+            // Check the admin proof and that there are enough authorizations for this operation
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::IncreaseMinAuthorizers,
+                arguments_hash(&(operation,)),
+            ) {
+                return;
+            }
+
+            let min_authorizers = self.get_min_authorizers(authorized_operation) + 1;
+
+            // Verify that enough admins exist or the multisig operations will be impossible!
+            assert!(
+                min_authorizers < self.number_of_admins,
+                "The minimum number of authorizers must be smaller than the number of badges",
+            );
+
+            self.min_authorizers.insert(authorized_operation, min_authorizers);
+        }
+
+        // Decrease by 1 the number of authorizations needed for the specified multisig operation
+        // (the decrease does not apply to this operation).
+        // This operation must be authorized by other admins and requires the strictest threshold;
+        // admins must agree on the operation whose threshold is decreased.
+        // Warning: if the threshold of an operation goes to zero, any admin will be able to
+        // perform it alone.
+        pub fn decrease_min_authorizers(
+            &mut self,
+            admin_proof: Proof,
+            operation: u8, // AuthorizedOperation
+        ) {
+            let authorized_operation: AuthorizedOperation = operation.into();
+            Self::check_min_authorizers_operation(authorized_operation);
+
+            // Check the admin proof and that there are enough authorizations for this operation
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::DecreaseMinAuthorizers,
+                arguments_hash(&(operation,)),
+            ) {
+                return;
+            }
+
+            let min_authorizers = self.get_min_authorizers(authorized_operation);
+            assert!(
+                min_authorizers > 0,
+                "The minimum number of authorizers is already zero",
+            );
+
+            self.min_authorizers.insert(authorized_operation, min_authorizers - 1);
+        }
+
+        // Mint a new admin badge and send it to the specified account.
+        // Admins must authorize this operation ad agree on the account address to send the badge
+        // to and on the name and role of the new admin.
+        pub fn mint_admin_badge(
+            &mut self,
+            admin_proof: Proof,
+            mut new_admin_account: Global<Account>,
+            name: String,
+            role: String,
+        ) {
+
+            // Check the admin proof and that there are enough authorizations for this operation
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::MintAdminBadge,
+                arguments_hash(&(new_admin_account, name.clone(), role.clone())),
+            ) {
+                return;
+            }
+
+            // Mint the new admin badge
+            self.number_of_admins += 1;
+            self.last_admin_id += 1;
+            let admin_badge = self.admin_badge_resource_manager.mint_non_fungible(
+                &NonFungibleLocalId::integer(self.last_admin_id.into()),
+                Admin {
+                    name: name,
+                    role: role,
+                },
+            );
+
+            // and send it to the specified account
+            new_admin_account.try_deposit_or_abort(
+                admin_badge.into(),
+                None
+            );
+        }
+
+        // Revoke an admin badge: it can no longer be used to authorize or perform any operation
+        // and the pending authorizations involving it are removed.
+        // The badge is also recalled from the vault containing it and burnt, so that it can't
+        // satisfy the OWNER role of the single admin operations either; the operation fails if
+        // the badge is not in that vault.
+        // The minimum numbers of authorizers are reduced if they are no longer smaller than the
+        // number of admins.
+        // Admins must authorize this operation and agree on the badge id and the vault address.
+        pub fn revoke_admin_badge(
+            &mut self,
+            admin_proof: Proof,
+            admin_badge_id: u8,                 // Numeric id of the admin badge to revoke
+            admin_badge_vault: InternalAddress, // Vault containing the badge
+        ) {
+
+            // Check the admin proof and that there are enough authorizations for this operation
+            let admin_id = self.get_admin_id(admin_proof);
+            if !self.check_operation_authorization(
+                admin_id,
+                AuthorizedOperation::RevokeAdminBadge,
+                arguments_hash(&(admin_badge_id, admin_badge_vault)),
+            ) {
+                // The operation has been queued: don't let the owner of the badge cancel it
+                self.operation_queue.last_mut().unwrap().target_admin_id = Some(admin_badge_id);
+
+                return;
+            }
+
+            // Make sure the badge exists and is not already revoked
+            assert!(
+                admin_badge_id > 0 &&
+                admin_badge_id <= self.last_admin_id &&
+                !self.revoked_admin_ids.contains(&admin_badge_id),
+                "Admin badge not found",
+            );
+
+            // Don't leave the component without admins
+            assert!(
+                self.number_of_admins > 1,
+                "Can't revoke the last admin badge",
+            );
+
+            self.revoked_admin_ids.push(admin_badge_id);
+            self.admin_signing_keys.remove(&admin_badge_id);
+            self.number_of_admins -= 1;
+
+            // Keep the minimum numbers of authorizers smaller than the number of admins or the
+            // multisig operations will be impossible
+            let number_of_admins = self.number_of_admins;
+            for min_authorizers in self.min_authorizers.values_mut() {
+                if *min_authorizers >= number_of_admins {
+                    *min_authorizers = number_of_admins - 1;
+                }
+            }
+
+            // Remove the pending authorizations involving the revoked badge
+            self.authorization_vector.retain(|authorization| {
+                let revoke = authorization.allower_admin_id == admin_badge_id ||
+                    authorization.allowed_admin_id == admin_badge_id;
+
+                if revoke {
+                    Runtime::emit_event(
+                        AuthorizationRevokedEvent {
+                            allower_admin_id: authorization.allower_admin_id,
+                            allowed_admin_id: authorization.allowed_admin_id,
+                            authorized_operation: authorization.authorized_operation,
+                            arguments_hash: authorization.arguments_hash,
+                        }
+                    );
+                }
+
+                !revoke
+            });
+
+            // Cancel the operations queued by the revoked badge
+            self.operation_queue.retain(|queued_operation| {
+                let cancel = queued_operation.allowed_admin_id == admin_badge_id;
+
+                if cancel {
+                    Runtime::emit_event(
+                        QueuedOperationCancelledEvent {
+                            queued_operation_id: queued_operation.id,
+                            canceller_admin_id: admin_id,
+                            allowed_admin_id: queued_operation.allowed_admin_id,
+                            authorized_operation: queued_operation.authorized_operation,
+                            arguments_hash: queued_operation.arguments_hash,
+                        }
+                    );
+                }
+
+                !cancel
+            });
+
+            // Recall the badge using the fund manager badge and burn it
+            let admin_badge_bucket: Bucket = self.fund_manager_badge_vault.authorize_with_amount(
+                1,
+                || scrypto_decode(
+                    &ScryptoVmV1Api::object_call_direct(
+                        admin_badge_vault.as_node_id(),
+                        NON_FUNGIBLE_VAULT_RECALL_NON_FUNGIBLES_IDENT,
+                        scrypto_encode(
+                            &NonFungibleVaultRecallNonFungiblesInput {
+                                non_fungible_local_ids: indexset!(
+                                    NonFungibleLocalId::integer(admin_badge_id.into())
+                                ),
+                            }
+                        ).unwrap(),
+                    )
+                ).unwrap()
+            );
+            assert!(
+                admin_badge_bucket.resource_address() == self.admin_badge_resource_manager.address(),
+                "Wrong vault",
+            );
+            NonFungibleBucket(admin_badge_bucket).burn();
+
+            // Emit the AdminBadgeRevokedEvent event
+            Runtime::emit_event(
+                AdminBadgeRevokedEvent {
+                    admin_badge_id: admin_badge_id,
+                    number_of_admins: self.number_of_admins,
+                    min_authorizers: self.min_authorizers.clone(),
+                }
             );
         }
 
@@ -833,6 +2637,11 @@ mod fund_manager {
             &mut self,
             amount: Decimal,
         ) { 
+            assert!(
+                !self.bot_operations_paused,
+                "Bot operations paused",
+            );
+
             self.validator_badge_vault
                 .authorize_with_non_fungibles(
                     &self.validator_badge_vault.non_fungible_local_ids(1),
@@ -845,6 +2654,12 @@ mod fund_manager {
         // The bot can invoke this method to complete the unlock of the Validator's owner LSUs and
         // start their unstake
         pub fn start_unstake(&mut self) {
+            assert!(
+                !self.bot_operations_paused,
+                "Bot operations paused",
+            );
+
+            self.accrue_management_fee();
 
             // Complete LSU unlock
             let lsu_bucket = self.validator_badge_vault
@@ -873,85 +2688,404 @@ mod fund_manager {
             );
             
             // Store the received claim NFT
+            self.store_claim_nft(claim_nft_bucket);
+        }
+
+        // Private method to put a claim NFT in claim_nft_vault and index its id
+        fn store_claim_nft(
+            &mut self,
+            claim_nft_bucket: NonFungibleBucket,
+        ) {
+            let claim_nft_id = claim_nft_bucket.non_fungible_local_id();
+            self.claim_nft_ids.insert(self.next_claim_nft_number, claim_nft_id.clone());
+            self.claim_nft_numbers.insert(claim_nft_id, self.next_claim_nft_number);
+            self.next_claim_nft_number += 1;
+
             self.claim_nft_vault.put(claim_nft_bucket);
         }
 
-        // Private method to find the name of the DeFi protocol position to invest in
-        fn find_where_to_deposit_to(&self) -> 
-            String // The name of the DeFi protocol position
-        {
-            let mut smallest_percentage_diff: Decimal = dec!(101);
-            let mut smallest_percentage_diff_name: Option<String> = None;
+        // Private method to take claim NFTs out of claim_nft_vault and remove their ids from the
+        // index
+        fn take_claim_nfts(
+            &mut self,
+            claim_nft_ids: &IndexSet<NonFungibleLocalId>,
+        ) -> NonFungibleBucket {
+            for claim_nft_id in claim_nft_ids.iter() {
+                let number = self.claim_nft_numbers.remove(claim_nft_id).expect("Claim NFT not found");
+                self.claim_nft_ids.remove(&number);
+            }
 
-            // Go through the list of DeFi protocols positions to find the one whose currently
-            // invested percentage of the fund is much below the desired percentage
-            for name in self.defi_protocols_list.iter() {
-                let defi_protocol = self.defi_protocols.get(&name).unwrap();
+            self.claim_nft_vault.take_non_fungibles(claim_nft_ids)
+        }
 
-                let percentage = 100 * defi_protocol.value / self.total_value;
-                let percentage_diff: Decimal = percentage - defi_protocol.desired_percentage;
+        // Private method to compute how much USD value can still be invested in a DeFi protocol
+        // position before hitting its caps, given the total value of the fund after the
+        // operation.
+        // The result is negative if the position is already above one of its caps.
+        fn headroom(
+            defi_protocol: &DefiProtocol,
+            total_value: Decimal,   // Total value of the fund after the operation
+        ) -> Decimal {
+            let mut headroom = Decimal::MAX;
+
+            if defi_protocol.max_value.is_some() {
+                headroom = headroom.min(defi_protocol.max_value.unwrap() - defi_protocol.value);
+            }
+            if defi_protocol.max_percentage.is_some() {
+                headroom = headroom.min(
+                    total_value * defi_protocol.max_percentage.unwrap() / 100 - defi_protocol.value
+                );
+            }
 
-                if percentage_diff < smallest_percentage_diff {
-                    smallest_percentage_diff = percentage_diff;
-                    smallest_percentage_diff_name = Some(name.to_string());
-                }
+            headroom
+        }
+
+        // This method returns how much USD value can still be invested in each DeFi protocol
+        // position before hitting its caps (negative if the position is above one of them)
+        pub fn defi_protocols_headroom(&self) -> HashMap<String, Decimal> {
+            let mut defi_protocols_headroom = HashMap::new();
+
+            for name in self.defi_protocols_list.iter() {
+                defi_protocols_headroom.insert(
+                    name.clone(),
+                    Self::headroom(&self.defi_protocols.get(&name).unwrap(), self.total_value)
+                );
             }
 
-            // Return the name of the DeFi protocol position, fail if there are no DeFi protocol
-            // positions
-            smallest_percentage_diff_name.unwrap()
+            defi_protocols_headroom
         }
 
-        // The bot can invoke this method to complete the unstake of a batch of LSUs and invest the
-        // resulting XRD in one of the existing DeFi protocol positions.
-        // The method will also mint new fund units to distribute.
-        // Some DeFi protocols may require data from the morpher oracle upon investment. Since the
-        // bot doesn't know which protocol the fund will invest in, it is advisable to always send
-        // all of the morpher oracle data to this method.
-        pub fn finish_unstake(
-            &mut self,
-            claim_nft_id: String, // String representation of the claim NFT id to unstake
-            morpher_data: HashMap<ResourceAddress, (String, String)>, 
-        ) {
-            // The bot must complete previous distributions before invoking this method
-            assert!(
-                self.fund_units_vault.amount() == Decimal::ZERO,
-                "Previous distribution was not finished",
-            );
+        // Private method to build the list of DeFi protocol positions to pass to the allocation
+        // strategy component
+        fn positions_snapshot(
+            &self,
+            total_value: Decimal,   // Total value of the fund used to compute the headroom
+        ) -> Vec<PositionSnapshot> {
+            let mut positions: Vec<PositionSnapshot> = vec![];
 
-            // Take the specified claim NFT out of the Vault
-            let claim_nft_bucket = self.claim_nft_vault.take_non_fungible(
-                &NonFungibleLocalId::String(StringNonFungibleLocalId::try_from(claim_nft_id).unwrap())
-            );
+            for name in self.defi_protocols_list.iter() {
+                let defi_protocol = self.defi_protocols.get(&name).unwrap();
+
+                positions.push(
+                    PositionSnapshot {
+                        name: name.clone(),
+                        value: defi_protocol.value,
+                        desired_percentage: defi_protocol.desired_percentage,
+                        headroom: Self::headroom(&defi_protocol, total_value),
+                        coin: defi_protocol.coin,
+                        other_coin: defi_protocol.other_coin,
+                    }
+                );
+            }
 
-            // Get the XRD out of it
-            let mut bucket = self.validator.claim_xrd(claim_nft_bucket);
+            positions
+        }
 
-            // Send a percentage of the XRD to the buyback fund account
-            let buyback_fund_bucket = bucket.take(
-                (bucket.amount() * self.buyback_fund_percentage) / 100
+        // Private method to get a deposit split from the allocation strategy component and
+        // verify that it makes sense
+        fn allocation_strategy_deposit_split(
+            &self,
+            amount: Decimal,    // USD value to deposit
+        ) -> Vec<(String, Decimal)> {
+            let new_total_value = self.total_value + amount;
+            let positions = self.positions_snapshot(new_total_value);
+
+            let deposit_split = self.allocation_strategy.unwrap().deposit_split(
+                positions.clone(),
+                self.total_value,
+                amount,
             );
-            self.buyback_fund_account.try_deposit_or_abort(
-                buyback_fund_bucket.into(),
-                None
+
+            // Each position must exist, be listed once and have room for its share; the shares
+            // must sum up to one
+            let mut total_share = Decimal::ZERO;
+            let mut names: Vec<&String> = vec![];
+            for (name, share) in deposit_split.iter() {
+                let position = positions
+                    .iter()
+                    .find(|position| position.name == *name)
+                    .expect("Invalid allocation strategy output");
+
+                assert!(
+                    !names.contains(&name) &&
+                    *share > Decimal::ZERO &&
+                    amount * *share <= position.headroom,
+                    "Invalid allocation strategy output",
+                );
+
+                names.push(name);
+                total_share += *share;
+            }
+            assert!(
+                names.len() > 0 &&
+                total_share > Decimal::ONE - ALLOCATION_TOLERANCE &&
+                total_share < Decimal::ONE + ALLOCATION_TOLERANCE,
+                "Invalid allocation strategy output",
             );
 
-            // Compute the amount of new fund units to mint to keep their value constant and mint
-            // them
-            let xrd_amount = bucket.amount();
-            let xrd_price = self.oracle_component.unwrap().get_price(
-                XRD,
-                morpher_data.clone()
+            deposit_split
+        }
+
+        // Private method to get a withdrawal plan from the allocation strategy component and
+        // verify that it makes sense
+        fn allocation_strategy_withdrawal_plan(
+            &self,
+            amount: Decimal,    // USD value to withdraw
+        ) -> Vec<(String, Decimal)> {
+            let positions = self.positions_snapshot(self.total_value);
+
+            let withdrawal_plan = self.allocation_strategy.unwrap().withdrawal_plan(
+                positions.clone(),
+                self.total_value,
+                amount,
             );
-            let (_, fund_unit_gross_value) = self.fund_unit_value();
-            self.fund_units_to_distribute = xrd_amount * xrd_price / fund_unit_gross_value;
-            self.fund_units_vault.put(
-                self.fund_unit_resource_manager.mint(self.fund_units_to_distribute + Decimal::ONE)
+
+            // Each position must exist, be listed once and have enough value; the total can't
+            // exceed amount
+            let mut total_value = Decimal::ZERO;
+            let mut names: Vec<&String> = vec![];
+            for (name, value) in withdrawal_plan.iter() {
+                let position = positions
+                    .iter()
+                    .find(|position| position.name == *name)
+                    .expect("Invalid allocation strategy output");
+
+                assert!(
+                    !names.contains(&name) &&
+                    *value > Decimal::ZERO &&
+                    *value <= position.value,
+                    "Invalid allocation strategy output",
+                );
+
+                names.push(name);
+                total_value += *value;
+            }
+            assert!(
+                names.len() > 0 &&
+                total_value <= amount,
+                "Invalid allocation strategy output",
             );
 
-            // Find th DeFi protocol position to invest in
-            let defi_protocol_name = self.find_where_to_deposit_to();
-            let mut defi_protocol = self.defi_protocols.get_mut(&defi_protocol_name).unwrap();
+            withdrawal_plan
+        }
+
+        // Private method to split a deposit of the given USD value among the DeFi protocol
+        // positions whose value is below the desired percentage.
+        // Each position receives a share proportional to its shortfall, limited by its caps;
+        // positions that would receive less than min_deposit_value are excluded, starting from the
+        // smallest one.
+        // If no position is below its desired value, the whole deposit goes to the position
+        // selected by find_where_to_deposit_to.
+        // If the shortfalls can't absorb the deposit because of the caps, the positions that are
+        // not at their caps receive a share proportional to their desired percentage instead,
+        // limited by their headroom.
+        fn find_where_to_deposit_to_split(
+            &self,
+            amount: Decimal,    // USD value to deposit
+        ) -> Vec<(
+            String,             // The name of the DeFi protocol position
+            Decimal,            // Share of amount to deposit in this position (0-1)
+        )> {
+            // Use the allocation strategy component, if set
+            if self.allocation_strategy.is_some() {
+                return self.allocation_strategy_deposit_split(amount);
+            }
+
+            // Compute the sum of the desired percentages; each percentage is considered as a share
+            // of this sum
+            let mut total_desired_percentage = Decimal::ZERO;
+            for name in self.defi_protocols_list.iter() {
+                total_desired_percentage += Decimal::from(self.defi_protocols.get(&name).unwrap().desired_percentage);
+            }
+
+            // Compute the shortfall of each DeFi protocol position after the deposit, limited by
+            // its headroom
+            let new_total_value = self.total_value + amount;
+            let mut shortfalls: Vec<(String, Decimal, Decimal)> = vec![]; // name, shortfall, headroom
+            let mut candidates: Vec<(String, Decimal, Decimal)> = vec![]; // name, desired percentage, headroom
+            let mut capped = false;
+            for name in self.defi_protocols_list.iter() {
+                let defi_protocol = self.defi_protocols.get(&name).unwrap();
+
+                let headroom = Self::headroom(&defi_protocol, new_total_value);
+                if headroom <= Decimal::ZERO {
+                    capped = true;
+                    continue;
+                }
+                candidates.push((
+                    name.to_string(),
+                    Decimal::from(defi_protocol.desired_percentage),
+                    headroom,
+                ));
+
+                if total_desired_percentage > Decimal::ZERO {
+                    let desired_value =
+                        new_total_value * defi_protocol.desired_percentage / total_desired_percentage;
+
+                    if desired_value > defi_protocol.value {
+                        let shortfall = desired_value - defi_protocol.value;
+                        capped = capped || shortfall > headroom;
+
+                        shortfalls.push((name.to_string(), shortfall.min(headroom), headroom));
+                    }
+                }
+            }
+
+            // If no position is below its desired value, fall back to the single position logic
+            if shortfalls.len() == 0 {
+                let name = self.find_where_to_deposit_to(amount, new_total_value);
+                if name.is_some() {
+                    return vec![(name.unwrap(), Decimal::ONE)];
+                }
+            }
+
+            // If the caps prevent the shortfalls from absorbing the whole deposit or no position is
+            // below its desired value, split it by desired percentage within the headrooms
+            let mut total_shortfall = shortfalls
+                .iter()
+                .fold(Decimal::ZERO, |total, (_, shortfall, _)| total + *shortfall);
+            if shortfalls.len() == 0 || (capped && total_shortfall < amount) {
+                shortfalls = Self::split_by_desired_percentage(candidates, amount);
+                total_shortfall = shortfalls
+                    .iter()
+                    .fold(Decimal::ZERO, |total, (_, share, _)| total + *share);
+            }
+
+            // Remove the positions that would receive less than min_deposit_value, always keep at
+            // least one position and never push the remaining ones above their caps
+            while shortfalls.len() > 1 {
+                let (smallest_index, smallest_shortfall) = shortfalls
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (_, shortfall, _))| (index, *shortfall))
+                    .min_by(|a, b| a.1.cmp(&b.1))
+                    .unwrap();
+
+                if amount * smallest_shortfall / total_shortfall >= self.min_deposit_value {
+                    break;
+                }
+
+                let remaining_shortfall = total_shortfall - smallest_shortfall;
+                if shortfalls
+                    .iter()
+                    .enumerate()
+                    .any(|(index, (_, shortfall, headroom))| {
+                        index != smallest_index &&
+                        amount * *shortfall / remaining_shortfall > *headroom
+                    }) {
+                    break;
+                }
+
+                shortfalls.remove(smallest_index);
+                total_shortfall = remaining_shortfall;
+            }
+
+            // Return the share of amount to deposit in each position
+            shortfalls
+                .into_iter()
+                .map(|(name, shortfall, _)| (name, shortfall / total_shortfall))
+                .collect()
+        }
+
+        // Private method to select the DeFi protocol position whose currently invested percentage
+        // of the fund is the most below its desired percentage among the ones that have room for
+        // the whole deposit; None is returned if no position has enough room
+        fn find_where_to_deposit_to(
+            &self,
+            amount: Decimal,            // USD value to deposit
+            new_total_value: Decimal,   // Total value of the fund after the deposit
+        ) -> Option<String>             // The name of the DeFi protocol position
+        {
+            let mut smallest_percentage_diff: Decimal = dec!(101);
+            let mut smallest_percentage_diff_name: Option<String> = None;
+
+            for name in self.defi_protocols_list.iter() {
+                let defi_protocol = self.defi_protocols.get(&name).unwrap();
+
+                if Self::headroom(&defi_protocol, new_total_value) < amount {
+                    continue;
+                }
+
+                let percentage = match self.total_value > Decimal::ZERO {
+                    true => 100 * defi_protocol.value / self.total_value,
+                    false => Decimal::ZERO,
+                };
+                let percentage_diff: Decimal = percentage - defi_protocol.desired_percentage;
+
+                if percentage_diff < smallest_percentage_diff {
+                    smallest_percentage_diff = percentage_diff;
+                    smallest_percentage_diff_name = Some(name.to_string());
+                }
+            }
+
+            smallest_percentage_diff_name
+        }
+
+        // Private method to split a USD value among DeFi protocol positions in proportion to
+        // their desired percentage (evenly if none of them has one) without exceeding their
+        // headroom: a position whose share would exceed its headroom receives exactly its
+        // headroom and the rest is split among the others
+        fn split_by_desired_percentage(
+            mut candidates: Vec<(String, Decimal, Decimal)>, // name, desired percentage, headroom
+            amount: Decimal,                                 // USD value to split
+        ) -> Vec<(
+            String,             // The name of the DeFi protocol position
+            Decimal,            // USD value to deposit in this position
+            Decimal,            // Headroom of the position
+        )> {
+            let mut split: Vec<(String, Decimal, Decimal)> = vec![];
+            let mut remaining_amount = amount;
+
+            loop {
+                assert!(
+                    candidates.len() > 0,
+                    "Not enough room in the DeFi protocol positions",
+                );
+
+                let mut total_weight = candidates
+                    .iter()
+                    .fold(Decimal::ZERO, |total, (_, weight, _)| total + *weight);
+                if total_weight == Decimal::ZERO {
+                    for candidate in candidates.iter_mut() {
+                        candidate.1 = Decimal::ONE;
+                    }
+                    total_weight = Decimal::from(candidates.len());
+                }
+
+                let capped_index = candidates
+                    .iter()
+                    .position(|(_, weight, headroom)| remaining_amount * *weight / total_weight > *headroom);
+                match capped_index {
+                    Some(index) => {
+                        let (name, _, headroom) = candidates.remove(index);
+                        remaining_amount -= headroom;
+                        split.push((name, headroom, headroom));
+                    },
+                    None => {
+                        for (name, weight, headroom) in candidates.into_iter() {
+                            if weight > Decimal::ZERO {
+                                split.push((name, remaining_amount * weight / total_weight, headroom));
+                            }
+                        }
+
+                        return split;
+                    },
+                }
+            }
+        }
+
+        // Private method to deposit a bucket of coins in a DeFi protocol position and update the
+        // cached values of the position and of the whole fund.
+        // If the DeFi protocol position doesn't accept the coins in the bucket, they are swapped
+        // for its main coin.
+        fn deposit_in_defi_protocol(
+            &mut self,
+            defi_protocol_name: &String,    // Name of the DeFi protocol position
+            mut bucket: FungibleBucket,     // Coins to deposit
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+        ) -> Decimal                        // New USD value of the DeFi protocol position
+        {
+            let mut defi_protocol = self.defi_protocols.get_mut(defi_protocol_name).unwrap();
 
             // Extracts the eventual morpher oracle data that this protocol needs
             let (message, signature) = match defi_protocol.needed_morpher_data {
@@ -966,9 +3100,9 @@ mod fund_manager {
             let coin_amount: Decimal;
             let other_coin_amount: Option<Decimal>;
 
-            // If the DeFi protocol position accepts XRD send them an get the new total amount of
-            // coin invested in it
-            if defi_protocol.coin == XRD {
+            // If the DeFi protocol position accepts the coins send them an get the new total
+            // amount of coin invested in it
+            if bucket.resource_address() == defi_protocol.coin {
                 (coin_amount, other_coin_amount) = self.fund_manager_badge_vault.authorize_with_amount(
                     1,
                     || defi_protocol.wrapper.deposit_coin(
@@ -979,7 +3113,7 @@ mod fund_manager {
                     )
                 );
 
-            } else if defi_protocol.other_coin == Some(XRD) {
+            } else if defi_protocol.other_coin == Some(bucket.resource_address()) {
                 let defi_protocol_coin = defi_protocol.coin;
 
                 (coin_amount, other_coin_amount) = self.fund_manager_badge_vault.authorize_with_amount(
@@ -992,8 +3126,8 @@ mod fund_manager {
                     )
                 );
 
-            // If the DeFi protocol position needs a different coin, swap the XRD for that coin and
-            // get the new total amount of coins invested in it
+            // If the DeFi protocol position needs a different coin, swap the coins for that coin
+            // and get the new total amount of coins invested in it
             } else {
                 bucket = self.fund_manager_badge_vault.authorize_with_amount(
                     1,
@@ -1018,752 +3152,3177 @@ mod fund_manager {
             }
 
             // Compute the new value of the DeFi protocol position
-            let mut new_protocol_value = match defi_protocol.coin {
-                XRD => coin_amount * xrd_price,
-                _ => coin_amount * self.oracle_component.unwrap().get_price(
-                    defi_protocol.coin,
-                    morpher_data.clone()
-                ),
-            };
+            let mut new_protocol_value = coin_amount * self.oracle_component.unwrap().get_price(
+                defi_protocol.coin,
+                morpher_data.clone()
+            );
             if other_coin_amount.is_some() {
-                if defi_protocol.other_coin.unwrap() == XRD {
-                    new_protocol_value += other_coin_amount.unwrap() * xrd_price;
-                } else {
-                    new_protocol_value += other_coin_amount.unwrap() * self.oracle_component.unwrap().get_price(
-                        defi_protocol.other_coin.unwrap(),
-                        morpher_data
-                    );
-                }
+                new_protocol_value += other_coin_amount.unwrap() * self.oracle_component.unwrap().get_price(
+                    defi_protocol.other_coin.unwrap(),
+                    morpher_data
+                );
             }
 
             // Update the values of the DeFi protocol position and the whole fund
             self.total_value += new_protocol_value - defi_protocol.value;
             defi_protocol.value = new_protocol_value;
+            defi_protocol.last_update = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
 
-            // Emit the LsuUnstakeCompletedEvent event
-            Runtime::emit_event(
-                LsuUnstakeCompletedEvent {
-                    xrd_amount: xrd_amount,
-                    defi_protocol_name: defi_protocol_name,
-                    fund_units_to_distribute: self.fund_units_to_distribute,
-                    protocol_value: new_protocol_value,
-                    total_value: self.total_value,
-                }
-            );
+            new_protocol_value
         }
 
-        // The bot can invoke this method to distribute the recently minted fund units.
-        // The stakers IndexMap must contain the percentage of fund units to send to each account.
-        // In order to avoid hitting transaction limits, this method can be called more than once
-        // splitting the stakers IndexMap into multiple parts; more_stakers must always be true
-        // except for the last invokation that terminates the distribution.
-        pub fn fund_units_distribution (
+        // Private method to split a bucket of coins among the DeFi protocol positions selected by
+        // find_where_to_deposit_to_split and deposit them
+        fn deposit_split(
             &mut self,
-            stakers: IndexMap<Global<Account>, Decimal>,
-            more_stakers: bool,
+            mut bucket: FungibleBucket,     // Coins to deposit
+            value: Decimal,                 // USD value of the coins
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+        ) -> (
+            IndexMap<String, Decimal>,      // Amount of coins deposited in each position
+            IndexMap<String, Decimal>,      // New USD value of each position
         ) {
-            // Create a new IndexMap specifying the amount of fund units per recipient
-            let mut distribution: IndexMap<Global<Account>, ResourceSpecifier> = IndexMap::new();
-            for (account, share) in stakers.iter() {
-                distribution.insert(
-                    *account,
-                    ResourceSpecifier::Fungible(*share * self.fund_units_to_distribute),
+            let amount = bucket.amount();
+            let deposit_plan = self.find_where_to_deposit_to_split(value);
+
+            let mut defi_protocols_amount: IndexMap<String, Decimal> = IndexMap::new();
+            let mut defi_protocols_value: IndexMap<String, Decimal> = IndexMap::new();
+            let last_index = deposit_plan.len() - 1;
+            for (index, (defi_protocol_name, share)) in deposit_plan.into_iter().enumerate() {
+                let coin_bucket = match index == last_index {
+                    true => bucket.take(bucket.amount()),
+                    false => bucket.take(amount * share),
+                };
+
+                defi_protocols_amount.insert(defi_protocol_name.clone(), coin_bucket.amount());
+
+                let new_protocol_value = self.deposit_in_defi_protocol(
+                    &defi_protocol_name,
+                    coin_bucket,
+                    morpher_data.clone(),
                 );
+
+                defi_protocols_value.insert(defi_protocol_name, new_protocol_value);
             }
+            bucket.drop_empty();
 
-            // Send all of the fund units to the AccountLocker for the distribution and get back
-            // any eventual remainings
-            let remainings = self.account_locker.airdrop(
-                distribution,
-                self.fund_units_vault.take_all().into(),
-                true,
+            (defi_protocols_amount, defi_protocols_value)
+        }
+
+        // The bot can invoke this method to complete the unstake of a batch of LSUs and invest the
+        // resulting XRD in one or more of the existing DeFi protocol positions.
+        // The method will also mint new fund units to distribute, unless the claim NFT comes from a
+        // deposit_lsu operation.
+        // Some DeFi protocols may require data from the morpher oracle upon investment. Since the
+        // bot doesn't know which protocol the fund will invest in, it is advisable to always send
+        // all of the morpher oracle data to this method.
+        pub fn finish_unstake(
+            &mut self,
+            claim_nft_id: String, // String representation of the claim NFT id to unstake
+            morpher_data: HashMap<ResourceAddress, (String, String)>, 
+        ) {
+            assert!(
+                !self.bot_operations_paused,
+                "Bot operations paused",
             );
 
-            // If the distribution is completed burn the remainings, else put them back in the
-            // vault for the next distribution batch
-            if more_stakers {
-                self.fund_units_vault.put(FungibleBucket(remainings.unwrap()));
-            } else if remainings.is_some() {
-                remainings.unwrap().burn();
+            self.accrue_management_fee();
+
+            let claim_nft_id = NonFungibleLocalId::String(
+                StringNonFungibleLocalId::try_from(claim_nft_id).unwrap()
+            );
+
+            // If the claim NFT comes from a deposit_lsu operation, fund units have already been
+            // minted for it
+            let prepaid_value = self.prepaid_claims.remove(&claim_nft_id);
+
+            // Take the specified claim NFT out of the Vault and get the XRD out of it
+            let mut claim_nft_ids: IndexSet<NonFungibleLocalId> = IndexSet::new();
+            claim_nft_ids.insert(claim_nft_id);
+            let bucket = self.claim_xrd(&claim_nft_ids);
+
+            match prepaid_value {
+                Some(value) => self.invest_unstaked_xrd(bucket, value, Bucket::new(XRD), morpher_data),
+                None => self.invest_unstaked_xrd(Bucket::new(XRD), Decimal::ZERO, bucket, morpher_data),
             }
         }
 
-        // Register the wrapper for a DeFi protocol and assign a name to it.
-        // If a wrapper with the same name is already registered, this method removes protocol
-        // tokens from the old wrapper and deposits them in the new one.
-        // This operation requires authorization from the other admins; they have to agree on the
-        // wrapper name too.
-        pub fn add_defi_protocol(
+        // The bot can invoke this method to complete the unstake of all of the claim NFTs in the
+        // fund whose claim epoch has been reached and invest the resulting XRD as finish_unstake
+        // does.
+        // The claim NFTs are examined in the order they were received, starting from the oldest
+        // one not completed yet; at most MAX_CLAIM_NFTS_SCAN claim NFTs are examined and the
+        // method can be invoked again to complete the remaining ones.
+        pub fn finish_matured_unstakes(
             &mut self,
-            admin_proof: Proof,
-            name: String, // The name to assign to the protocol wrapper
-            coin: ResourceAddress, // The main coin managed by the new protocol
-            protocol_token: ResourceAddress, // The token belonging to this DeFi protocol
-            other_coin: Option<ResourceAddress>, // Eventual other coin managed by the protocol
-            desired_percentage: u8, // The percentage of the fund value that we want to be
-                                    // deposited in this protocol
-            wrapper: DefiProtocolInterfaceScryptoStub, // Component address of the wrapper
-            needed_morpher_data: Option<ResourceAddress>, // Whether the protocol needs data from
-                                                          // the Morpher oracle
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
         ) {
-
-            // Check that there are enough authorizations for this operation.
-            self.check_operation_authorization(
-                self.get_admin_id(admin_proof),
-                AuthorizedOperation::AddDefiProtocol,
-                Some(name.clone()),
-                None,
-                None,
+            assert!(
+                !self.bot_operations_paused,
+                "Bot operations paused",
             );
 
-            let mut old_defi_protocol: Option<DefiProtocol> = None;
+            self.accrue_management_fee();
 
-            // If there's no wrapper with the same name check that the list isn't getting too big
-            // then add the name to the list
-            if self.defi_protocols_list.iter().position(|n| *n == name).is_none() {
-                assert!(
-                    self.defi_protocols_list.len() < MAX_VECTOR_SIZE,
-                    "Protocols list is getting too big",
-                );
+            // Find the matured claim NFTs, separating the ones coming from deposit_lsu operations
+            let claim_nft_resource_manager = NonFungibleResourceManager::from(
+                self.claim_nft_vault.resource_address()
+            );
+            let current_epoch = Runtime::current_epoch();
+            let mut prepaid_claim_nft_ids: IndexSet<NonFungibleLocalId> = IndexSet::new();
+            let mut prepaid_value = Decimal::ZERO;
+            let mut claim_nft_ids: IndexSet<NonFungibleLocalId> = IndexSet::new();
+            let mut examined_claim_nfts: usize = 0;
+            let mut skip_first = true;
+            let mut number = self.first_claim_nft_number;
+            while number < self.next_claim_nft_number && examined_claim_nfts < MAX_CLAIM_NFTS_SCAN {
+                let claim_nft_id = self.claim_nft_ids.get(&number).map(|claim_nft_id| claim_nft_id.clone());
+                number += 1;
+
+                // Skip the numbers of the claim NFTs already taken out of the vault
+                let claim_nft_id = match claim_nft_id {
+                    None => {
+                        if skip_first {
+                            self.first_claim_nft_number = number;
+                        }
+                        continue;
+                    },
+                    Some(claim_nft_id) => claim_nft_id,
+                };
+                examined_claim_nfts += 1;
 
-                self.defi_protocols_list.push(name.clone());
+                let unstake_data = claim_nft_resource_manager.get_non_fungible_data::<UnstakeData>(&claim_nft_id);
+                if unstake_data.claim_epoch > current_epoch {
+                    skip_first = false;
+                    continue;
+                }
+                if skip_first {
+                    self.first_claim_nft_number = number;
+                }
 
-            // Otherwise get info about the existing wrapper position
-            } else {
-                old_defi_protocol= self.defi_protocols.remove(&name);
+                match self.prepaid_claims.remove(&claim_nft_id) {
+                    Some(value) => {
+                        prepaid_value += value;
+                        prepaid_claim_nft_ids.insert(claim_nft_id);
+                    },
+                    None => {
+                        claim_nft_ids.insert(claim_nft_id);
+                    },
+                }
             }
+            assert!(
+                prepaid_claim_nft_ids.len() + claim_nft_ids.len() > 0,
+                "No matured claim NFT",
+            );
 
-            // Create the new protocol wrapper position
-            let mut new_defi_protocol = DefiProtocol {
-                value: Decimal::ZERO,
-                desired_percentage: desired_percentage,
-                wrapper: wrapper,
-                coin: coin,
-                protocol_token: protocol_token,
-                other_coin: other_coin,
-                needed_morpher_data: needed_morpher_data,
-            };
-
-            // Get liquidity from the old protocol wrapper position and deposit it in the new one
-            if old_defi_protocol.is_some() {
-                new_defi_protocol.value = old_defi_protocol.as_ref().unwrap().value;
+            // Get the XRD out of the claim NFTs
+            let prepaid_bucket = self.claim_xrd(&prepaid_claim_nft_ids);
+            let bucket = self.claim_xrd(&claim_nft_ids);
 
-                self.fund_manager_badge_vault.authorize_with_amount(
-                    1,
-                    || {
-                        let (token_bucket, coin_bucket, other_coin_bucket) =
-                            old_defi_protocol.unwrap().wrapper.withdraw_all();
+            self.invest_unstaked_xrd(prepaid_bucket, prepaid_value, bucket, morpher_data);
+        }
 
-                        new_defi_protocol.wrapper.deposit_all(
-                            token_bucket,
-                            coin_bucket,
-                            other_coin_bucket,
-                        );
-                    }
-                );
+        // Private method to get the XRD out of the specified claim NFTs in claim_nft_vault
+        fn claim_xrd(
+            &mut self,
+            claim_nft_ids: &IndexSet<NonFungibleLocalId>,
+        ) -> Bucket {
+            if claim_nft_ids.len() == 0 {
+                return Bucket::new(XRD);
             }
 
-            // Add the new DeFi protocol wrapper position in the KeyValueStore
-            self.defi_protocols.insert(
-                name,
-                new_defi_protocol,
-            );
+            let claim_nft_bucket = self.take_claim_nfts(claim_nft_ids);
+
+            self.validator.claim_xrd(claim_nft_bucket)
         }
 
-        // An admin can invoke this method to deposit coins in an existing DeFi protocol and
-        // eventually mint new fund units corresponding to the value of the added coins.
-        // There's no need for authorization; a single admin can invoke this method.
-        pub fn deposit_coin(
-            &mut self,
-            defi_protocol_name: String, // The name of the protocol to deposit the coin in
-            coin_bucket: FungibleBucket, // Bucket of coin to deposit
-            other_coin_bucket: Option<FungibleBucket>, // Eventual additional bucket of coin
+        // Returns the claim NFTs held by the fund, in the order they were received, with their
+        // number, their claim epoch, the amount of XRD they will be redeemed for and whether they
+        // come from a deposit_lsu operation.
+        // At most MAX_CLAIM_NFTS_SCAN claim NFTs are returned; call the method again with
+        // start_number set to the last returned number plus one to get the following ones.
+        pub fn pending_claims(
+            &self,
+            start_number: Option<u64>, // Number of the first claim NFT to return, if known
+        ) -> Vec<(
+            u64,                // Claim NFT number
+            NonFungibleLocalId, // Claim NFT id
+            u64,                // Claim epoch
+            Decimal,            // XRD amount
+            bool,               // Whether it comes from a deposit_lsu operation
+        )> {
+            let claim_nft_resource_manager = NonFungibleResourceManager::from(
+                self.claim_nft_vault.resource_address()
+            );
 
-            // Eventual Morpher data required by the protocol or the oracle component
-            morpher_data: HashMap<ResourceAddress, (String, String)>,
+            let mut claims = vec![];
 
-            mint_fund_units: bool, // Whether to mint new fund units whose value is equal to the
-                                   // one of the deposited coins
-        ) -> Option<FungibleBucket> // Fund units
-        {
+            let mut number = self.first_claim_nft_number;
+            if start_number.is_some() {
+                number = number.max(start_number.unwrap());
+            }
+            while number < self.next_claim_nft_number && claims.len() < MAX_CLAIM_NFTS_SCAN {
+                let claim_nft_id = self.claim_nft_ids.get(&number).map(|claim_nft_id| claim_nft_id.clone());
+                if claim_nft_id.is_some() {
+                    let claim_nft_id = claim_nft_id.unwrap();
+                    let unstake_data = claim_nft_resource_manager.get_non_fungible_data::<UnstakeData>(&claim_nft_id);
+
+                    claims.push((
+                        number,
+                        claim_nft_id.clone(),
+                        unstake_data.claim_epoch.number(),
+                        unstake_data.claim_amount,
+                        self.prepaid_claims.get(&claim_nft_id).is_some(),
+                    ));
+                }
+                number += 1;
+            }
 
-            // Compute the USD value of the first bucket of deposited coins
-            let coin_price = self.oracle_component.unwrap().get_price(
-                coin_bucket.resource_address(),
-                morpher_data.clone(),
+            claims
+        }
+
+        // Private method to invest the XRD coming from matured claim NFTs in one or more of the
+        // existing DeFi protocol positions.
+        // The fund units for the claim NFTs coming from deposit_lsu operations have already been
+        // minted and their value (prepaid_value) is already included in the fund total value; it
+        // is removed as the invested XRD will replace it.
+        // For the other XRD (new_bucket) a percentage is sent to the buyback fund and new fund
+        // units to distribute are minted.
+        fn invest_unstaked_xrd(
+            &mut self,
+            mut bucket: Bucket,             // XRD from claim NFTs coming from deposit_lsu
+            prepaid_value: Decimal,         // Value of the claim NFTs coming from deposit_lsu
+            mut new_bucket: Bucket,         // XRD from the other claim NFTs
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+        ) {
+            let xrd_price = self.oracle_component.unwrap().get_price(
+                XRD,
+                morpher_data.clone()
             );
-            let mut buckets_value = coin_bucket.amount() * coin_price;
 
-            // Get the current value of a fund unit
-            let (_, fund_unit_gross_value) = self.fund_unit_value();
+            let fund_units_to_distribute = match new_bucket.amount() == Decimal::ZERO {
+                true => Decimal::ZERO,
 
-            // Get information about the DeFi protocol to deposit the buckets in
-            let mut defi_protocol = self.defi_protocols.get_mut(&defi_protocol_name).expect("Protocol not found");
+                false => {
+                    // The bot must complete previous distributions before investing new XRD
+                    assert!(
+                        self.fund_units_vault.amount() == Decimal::ZERO,
+                        "Previous distribution was not finished",
+                    );
 
-            // Extract the Morpher data needed by the DeFi protocol from the ones received
-            let (message, signature) = match defi_protocol.needed_morpher_data {
-                Some(resource_address) => {
-                    let morpher_data_needed_by_protocol = morpher_data.get(&resource_address).expect("Missing needed morpher data").clone();
-                    
-                    (Some(morpher_data_needed_by_protocol.0), Some(morpher_data_needed_by_protocol.1))
-                },
-                None => (None, None),
-            };
+                    // Send a percentage of the XRD to the buyback fund account
+                    let buyback_fund_bucket = new_bucket.take(
+                        (new_bucket.amount() * self.buyback_fund_percentage) / 100
+                    );
+                    self.buyback_fund_account.try_deposit_or_abort(
+                        buyback_fund_bucket.into(),
+                        None
+                    );
 
-            // If the protocol handles two coins update buckets_value so that it contains the USD
-            // value of both buckets
-            let other_coin_price = match defi_protocol.other_coin {
-                None => None,
-                Some(other_coin) => {
-                    let other_coin_price = self.oracle_component.unwrap().get_price(
-                        other_coin,
-                        morpher_data
+                    // Compute the amount of new fund units to mint to keep their value constant
+                    // and mint them
+                    self.refresh_stale_defi_protocols_value(morpher_data.clone());
+                    self.crystallize_performance_fee();
+                    let fund_unit_gross_value = self.fund_unit_mint_value();
+                    self.fund_units_to_distribute = new_bucket.amount() * xrd_price / fund_unit_gross_value;
+                    self.fund_units_vault.put(
+                        self.fund_unit_resource_manager.mint(self.fund_units_to_distribute + Decimal::ONE)
                     );
 
-                    if other_coin_bucket.is_some() {
-                        buckets_value += other_coin_bucket.as_ref().unwrap().amount() * other_coin_price;
-                    }
+                    // Add the fund units reclaimed from the AccountLocker to the distribution
+                    self.fund_units_to_distribute += self.rolled_over_fund_units_vault.amount();
+                    self.fund_units_vault.put(self.rolled_over_fund_units_vault.take_all());
 
-                    Some(other_coin_price)
+                    self.fund_units_to_distribute
                 },
             };
 
-            // Deposit the buckets in the DeFi protocol and get the total number of coins invested
-            let (coin_amount, other_coin_amount) = self.fund_manager_badge_vault.authorize_with_amount(
-                1,
-                || defi_protocol.wrapper.deposit_coin(
-                    coin_bucket,
-                    other_coin_bucket,
-                    message,
-                    signature,
-                )
+            // Remove the value of the prepaid claim NFTs only after the new fund units have been
+            // minted, as the XRD replacing it are not deposited yet
+            self.total_value -= prepaid_value;
+
+            bucket.put(new_bucket);
+            let xrd_amount = bucket.amount();
+
+            // Split the XRD among the DeFi protocol positions to invest in
+            let (defi_protocols_xrd_amount, defi_protocols_value) = self.deposit_split(
+                bucket,
+                xrd_amount * xrd_price,
+                morpher_data,
             );
 
-            // Compute the new value of the DeFi protocol position and update cached values 
-            // information accordingly
-            let mut new_protocol_value = coin_amount * coin_price;
-            if other_coin_amount.is_some() {
-                new_protocol_value += other_coin_amount.unwrap() * other_coin_price.unwrap();
+            // Find the DeFi protocol that received most of the XRD
+            let mut defi_protocol_name = String::new();
+            let mut largest_xrd_amount = Decimal::MIN;
+            for (name, amount) in defi_protocols_xrd_amount.iter() {
+                if *amount > largest_xrd_amount {
+                    largest_xrd_amount = *amount;
+                    defi_protocol_name = name.to_string();
+                }
             }
-            self.total_value += new_protocol_value - defi_protocol.value;
-            defi_protocol.value = new_protocol_value;
+            let protocol_value = *defi_protocols_value.get(&defi_protocol_name).unwrap();
 
-            // Emit the AdminDepositEvent event
+            // Emit the LsuUnstakeCompletedEvent event
             Runtime::emit_event(
-                AdminDepositEvent {
+                LsuUnstakeCompletedEvent {
+                    xrd_amount: xrd_amount,
                     defi_protocol_name: defi_protocol_name,
-                    protocol_value: new_protocol_value,
+                    fund_units_to_distribute: fund_units_to_distribute,
+                    protocol_value: protocol_value,
                     total_value: self.total_value,
+                    defi_protocols_xrd_amount: defi_protocols_xrd_amount,
+                    defi_protocols_value: defi_protocols_value,
                 }
             );
 
-            // Mint the new fund units if required
-            if mint_fund_units {
-                Some(self.fund_unit_resource_manager.mint(buckets_value / fund_unit_gross_value))
-            } else {
-                None
-            }
+            self.record_nav_checkpoint();
         }
 
-        // An admin can invoke this method to deposit protocol tokens in an existing DeFi protocol
-        // and eventually mint new fund units corresponding to the value of the added tokens.
-        // There's no need for authorization; a single admin can invoke this method.
-        pub fn deposit_protocol_token(
+        // Before distributing the recently minted fund units, the bot must invoke this method to
+        // commit the hash of the whole distribution plan (see distribution_plan_hash) and the
+        // number of stakers in it.
+        // The plan can be replaced until the first batch is distributed.
+        pub fn commit_distribution_plan(
             &mut self,
-            defi_protocol_name: String, // The name of the protocol to deposit the tokens in
-            protocol_token_bucket: Bucket, // The bicket of tokens to deposit
+            plan_hash: Hash,
+            number_of_stakers: u64,
+        ) -> u64 {
+            assert!(
+                !self.bot_operations_paused,
+                "Bot operations paused",
+            );
+            assert!(
+                self.fund_units_vault.amount() > Decimal::ZERO,
+                "Nothing to distribute",
+            );
+            assert!(
+                number_of_stakers > 0,
+                "No stakers",
+            );
+            assert!(
+                self.distribution_plan.is_none() ||
+                self.distribution_plan.as_ref().unwrap().paid_stakers == 0,
+                "Distribution in progress",
+            );
 
-            // Eventual Morpher data required by the protocol or the oracle component
-            morpher_data: HashMap<ResourceAddress, (String, String)>,
+            self.last_distribution_plan_id += 1;
+            self.distribution_plan = Some(
+                DistributionPlan {
+                    id: self.last_distribution_plan_id,
+                    plan_hash: plan_hash,
+                    number_of_stakers: number_of_stakers,
+                    running_hash: Hash([0u8; 32]),
+                    cumulative_share: Decimal::ZERO,
+                    paid_stakers: 0,
+                }
+            );
 
-            mint_fund_units: bool, // Whether to mint new fund units or not
-        ) -> Option<FungibleBucket> // Fund units
-        {
-            // Get information about the DeFi protocol to deposit the bucket in
-            let mut defi_protocol = self.defi_protocols.get_mut(&defi_protocol_name).unwrap();
+            // Emit the DistributionPlanCommittedEvent event
+            Runtime::emit_event(
+                DistributionPlanCommittedEvent {
+                    distribution_id: self.last_distribution_plan_id,
+                    plan_hash: plan_hash,
+                    number_of_stakers: number_of_stakers,
+                    fund_unit_amount: self.fund_units_to_distribute,
+                }
+            );
 
-            // Get the current amount of coins invested in this DeFi protocol position
-            let (old_coin_amount, old_other_coin_amount) = defi_protocol.wrapper.get_coin_amounts();
+            self.last_distribution_plan_id
+        }
 
-            // Deposit the tokens and get the updated number of coins
-            let (coin_amount, other_coin_amount) = self.fund_manager_badge_vault.authorize_with_amount(
-                1,
-                || defi_protocol.wrapper.deposit_all(protocol_token_bucket, None, None)
+        // Returns the id, the committed hash and number of stakers, the running hash, the sum of
+        // the shares and the number of stakers paid so far of the distribution plan in progress
+        pub fn distribution_plan(&self) -> Option<(u64, Hash, u64, Hash, Decimal, u64)> {
+            self.distribution_plan.as_ref().map(|plan| (
+                plan.id,
+                plan.plan_hash,
+                plan.number_of_stakers,
+                plan.running_hash,
+                plan.cumulative_share,
+                plan.paid_stakers,
+            ))
+        }
+
+        // The bot can invoke this method to distribute the recently minted fund units according
+        // to the committed distribution plan.
+        // The stakers IndexMap must contain the share (0-1) of fund units to send to each account.
+        // In order to avoid hitting transaction limits, this method can be called more than once
+        // splitting the stakers IndexMap into multiple parts; more_stakers must always be true
+        // except for the last invokation that terminates the distribution.
+        // The distribution can't be terminated unless the stakers match the committed plan, so that
+        // no account is paid twice, and their shares add up to one.
+        pub fn fund_units_distribution (
+            &mut self,
+            stakers: IndexMap<Global<Account>, Decimal>,
+            more_stakers: bool,
+        ) {
+            assert!(
+                !self.bot_operations_paused,
+                "Bot operations paused",
             );
 
-            // Compute the protocol value immediately before and after the deposit
-            let coin_price = self.oracle_component.unwrap().get_price(
-                defi_protocol.coin,
-                morpher_data.clone()
+            let mut plan = self.distribution_plan.take().expect("No distribution plan");
+
+            // Create a new IndexMap specifying the amount of fund units (or the vesting receipt)
+            // per recipient and update the plan progress
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+            let mut distribution: IndexMap<Global<Account>, ResourceSpecifier> = IndexMap::new();
+            let mut vesting_receipts_bucket = NonFungibleBucket::new(
+                self.vesting_receipt_resource_manager.address()
             );
-            let mut protocol_value = coin_price * old_coin_amount;
-            let mut new_protocol_value = coin_price * coin_amount;
-            if defi_protocol.other_coin.is_some() {
-                let other_coin_price = self.oracle_component.unwrap().get_price(
-                    defi_protocol.other_coin.unwrap(),
-                    morpher_data
+            let mut fund_unit_amount = Decimal::ZERO;
+            for (account, share) in stakers.iter() {
+                assert!(
+                    *share > Decimal::ZERO,
+                    "Share must be positive",
                 );
 
-                protocol_value += other_coin_price * old_other_coin_amount.unwrap();
-                new_protocol_value += other_coin_price * other_coin_amount.unwrap();
+                plan.running_hash = distribution_plan_hash(plan.running_hash, *account, *share);
+                plan.cumulative_share += *share;
+                plan.paid_stakers += 1;
+
+                let amount = *share * self.fund_units_to_distribute;
+                fund_unit_amount += amount;
+
+                // In vesting mode send a vesting receipt instead of the fund units
+                if self.vesting_period > 0 {
+                    self.last_vesting_receipt_id += 1;
+                    let vesting_receipt_id = NonFungibleLocalId::integer(self.last_vesting_receipt_id);
+                    vesting_receipts_bucket.put(
+                        self.vesting_receipt_resource_manager.mint_non_fungible(
+                            &vesting_receipt_id,
+                            VestingReceipt {
+                                fund_unit_amount: amount,
+                                start: now,
+                                end: now + self.vesting_period,
+                                claimed_amount: Decimal::ZERO,
+                            },
+                        )
+                    );
+                    distribution.insert(
+                        *account,
+                        ResourceSpecifier::NonFungible(indexset!(vesting_receipt_id)),
+                    );
+                } else {
+                    distribution.insert(
+                        *account,
+                        ResourceSpecifier::Fungible(amount),
+                    );
+                }
             }
+            assert!(
+                plan.cumulative_share <= Decimal::ONE + DISTRIBUTION_SHARES_TOLERANCE,
+                "Shares exceed one",
+            );
+            assert!(
+                plan.paid_stakers <= plan.number_of_stakers,
+                "Too many stakers",
+            );
 
-            // Update cached value information
-            self.total_value += new_protocol_value - defi_protocol.value;
-            defi_protocol.value = new_protocol_value;
+            // Send everything to the distribution AccountLocker: it keeps what the accounts refuse.
+            // In vesting mode the fund units stay in the vesting vault
+            if self.vesting_period > 0 {
+                self.vesting_vault.put(self.fund_units_vault.take(fund_unit_amount));
+                let remainings = self.distribution_account_locker.airdrop(
+                    distribution,
+                    vesting_receipts_bucket.into(),
+                    true,
+                );
+                if remainings.is_some() {
+                    remainings.unwrap().drop_empty();
+                }
+            } else {
+                vesting_receipts_bucket.drop_empty();
+                let remainings = self.distribution_account_locker.airdrop(
+                    distribution,
+                    self.fund_units_vault.take_all().into(),
+                    true,
+                );
+                if remainings.is_some() {
+                    self.fund_units_vault.put(FungibleBucket(remainings.unwrap()));
+                }
+            }
 
-            // Emit the AdminDepositEvent event
+            // Emit the FundUnitsDistributedEvent event
             Runtime::emit_event(
-                AdminDepositEvent {
-                    defi_protocol_name: defi_protocol_name,
-                    protocol_value: new_protocol_value,
-                    total_value: self.total_value,
+                FundUnitsDistributedEvent {
+                    distribution_id: plan.id,
+                    number_of_stakers: u64::try_from(stakers.len()).unwrap(),
+                    fund_unit_amount: fund_unit_amount,
+                    cumulative_share: plan.cumulative_share,
                 }
             );
 
-            // If requested, mint new fund units corresponding to the increase value because of
-            // the deposit
-            match mint_fund_units {
-                false => None,
-                true => {
-                    drop(defi_protocol);
+            // If the distribution is completed check it against the plan and burn the remainings,
+            // else keep them in the vault for the next distribution batch
+            if more_stakers {
+                self.distribution_plan = Some(plan);
+            } else {
+                assert!(
+                    plan.paid_stakers == plan.number_of_stakers &&
+                    plan.running_hash == plan.plan_hash,
+                    "Distribution doesn't match the plan",
+                );
+                assert!(
+                    plan.cumulative_share >= Decimal::ONE - DISTRIBUTION_SHARES_TOLERANCE,
+                    "Shares don't add up to one",
+                );
 
-                    let (_, fund_unit_gross_value) = self.fund_unit_value();
+                let burnt_fund_units = self.fund_units_vault.amount();
+                self.fund_units_vault.take_all().burn();
 
-                    Some(
-                        self.fund_unit_resource_manager.mint(
-                            (new_protocol_value - protocol_value) / fund_unit_gross_value
-                        )
-                    )
-                },
+                // Emit the FundUnitsDistributionCompletedEvent event
+                Runtime::emit_event(
+                    FundUnitsDistributionCompletedEvent {
+                        distribution_id: plan.id,
+                        number_of_stakers: plan.paid_stakers,
+                        cumulative_share: plan.cumulative_share,
+                        burnt_fund_units: burnt_fund_units,
+                    }
+                );
             }
         }
 
-        // Removes a DeFi protocol wrapper and returns the badge to control the Account used by the
-        // wrapper
-        // An admin can perform this operation only when authorized by the other admins
-        pub fn remove_defi_protocol(
+        // Private method to increase the fund units of a distribution plan known to be in the
+        // AccountLocker
+        fn track_unclaimed_distribution(
             &mut self,
-            admin_proof: Proof,
-            name: String,
-        ) -> NonFungibleBucket {
-
-            // Check other admins' authorizations
-            self.check_operation_authorization(
-                self.get_admin_id(admin_proof),
-                AuthorizedOperation::RemoveDefiProtocol,
-                Some(name.clone()),
-                None,
-                None,
-            );
+            distribution_id: u64,
+            amount: Decimal,
+            now: i64,
+        ) {
+            if self.unclaimed_distributions.get(&distribution_id).is_some() {
+                self.unclaimed_distributions.get_mut(&distribution_id).unwrap().amount += amount;
+            } else {
+                self.unclaimed_distributions.insert(
+                    distribution_id,
+                    UnclaimedDistribution {
+                        timestamp: now,
+                        amount: amount,
+                    }
+                );
+                self.unclaimed_distribution_ids.push(distribution_id);
+            }
+        }
 
-            // Remove the protocol position from the Vector and the KeyValueStore
-            self.defi_protocols_list.retain(|n| { *n != name });
-            let mut defi_protocol = self.defi_protocols.remove(&name)
-                .expect("Protocol not found");
+        // Private method to decrease the fund units of a distribution plan known to be in the
+        // AccountLocker
+        fn untrack_unclaimed_distribution(
+            &mut self,
+            distribution_id: u64,
+            amount: Decimal,
+        ) {
+            let remaining_amount = {
+                let mut unclaimed_distribution = self.unclaimed_distributions.get_mut(&distribution_id).unwrap();
+                unclaimed_distribution.amount -= amount;
 
-            // Update the total fund value and emit a RemovedProtocolEvent event containing this
-            // information
-            self.total_value -= defi_protocol.value;
-            Runtime::emit_event(
-                RemovedProtocolEvent {
-                    defi_protocol_name: name,
-                    total_value: self.total_value,
-                }
-            );
+                unclaimed_distribution.amount
+            };
 
-            // Get the Account badge
-            self.fund_manager_badge_vault.authorize_with_amount(
-                1,
-                || defi_protocol.wrapper.withdraw_account_badge()
-            )
+            if remaining_amount <= Decimal::ZERO {
+                self.unclaimed_distributions.remove(&distribution_id);
+                self.unclaimed_distribution_ids.retain(|id| *id != distribution_id);
+            }
         }
 
-        // Updates the cached value of the specified DeFi protocols by asking amounts to the
-        // protocols themselves and prices to the oracle component
-        pub fn update_defi_protocols_value(
+        // The bot can invoke this method to reclaim from the AccountLocker the fund units that
+        // fund_units_distribution left there for the specified accounts and that have not been
+        // claimed within unclaimed_fund_units_expiry.
+        // The fund units found in the AccountLocker for the first time are attributed to the last
+        // distribution plan and their expiry starts now, so the bot should also invoke this
+        // method for the accounts that refused the fund units right after each distribution.
+        // The reclaimed fund units are burnt or, if roll_over is true, added to the next
+        // distribution.
+        pub fn reclaim_unclaimed_fund_units(
             &mut self,
-            defi_protocols: IndexSet<String>,
-            morpher_data: HashMap<ResourceAddress, (String, String)>,
+            accounts: Vec<Global<Account>>,
+            roll_over: bool,
         ) {
-            // Variable to store the total fund value change
-            let mut value_change = Decimal::ZERO;
+            assert!(
+                !self.bot_operations_paused,
+                "Bot operations paused",
+            );
 
-            // Coin prices cache
-            let mut prices: HashMap<ResourceAddress, Decimal> = HashMap::new();
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+            let fund_unit = self.fund_unit_resource_manager.address();
+
+            for account in accounts.iter() {
+                let mut unclaimed_fund_units = self.unclaimed_fund_units.remove(&account.address())
+                    .map(|unclaimed_fund_units| unclaimed_fund_units.amounts)
+                    .unwrap_or(IndexMap::new());
+
+                // Nothing else stores fund units in the distribution AccountLocker, so any
+                // difference from the tracked amount was either left there by the last
+                // distributions or claimed by the account
+                let tracked_amount = unclaimed_fund_units.values().fold(Decimal::ZERO, |sum, amount| sum + *amount);
+                let locker_amount = self.distribution_account_locker.get_amount(*account, fund_unit);
+                if locker_amount > tracked_amount {
+                    *unclaimed_fund_units.entry(self.last_distribution_plan_id).or_insert(Decimal::ZERO) +=
+                        locker_amount - tracked_amount;
+                    self.track_unclaimed_distribution(
+                        self.last_distribution_plan_id,
+                        locker_amount - tracked_amount,
+                        now,
+                    );
+                }
 
-            // For each protocol
-            for name in defi_protocols.iter() {
-                let mut defi_protocol = self.defi_protocols.get_mut(&name).unwrap();
+                // The account may have claimed part of its fund units; consider the oldest ones
+                // claimed
+                let mut claimed_amount = tracked_amount - locker_amount;
+                let mut reclaimed_amounts: IndexMap<u64, Decimal> = IndexMap::new();
+                let mut reclaimed_amount = Decimal::ZERO;
+                for (distribution_id, amount) in unclaimed_fund_units.iter_mut() {
+                    if claimed_amount > Decimal::ZERO {
+                        let claimed = claimed_amount.min(*amount);
+                        *amount -= claimed;
+                        claimed_amount -= claimed;
+                        self.untrack_unclaimed_distribution(*distribution_id, claimed);
+                    }
 
-                // Get the coin amounts
-                let (coin_amount, other_coin_amount) = defi_protocol.wrapper.get_coin_amounts();
+                    // Reclaim the fund units whose distribution is expired
+                    let timestamp = self.unclaimed_distributions.get(distribution_id)
+                        .map(|unclaimed_distribution| unclaimed_distribution.timestamp);
+                    if *amount > Decimal::ZERO &&
+                        timestamp.is_some() &&
+                        timestamp.unwrap() + self.unclaimed_fund_units_expiry <= now {
+                        reclaimed_amounts.insert(*distribution_id, *amount);
+                        reclaimed_amount += *amount;
+                        self.untrack_unclaimed_distribution(*distribution_id, *amount);
+                        *amount = Decimal::ZERO;
+                    }
+                }
+                unclaimed_fund_units.retain(|_, amount| *amount > Decimal::ZERO);
+
+                // Keep track of the fund units not expired yet
+                if unclaimed_fund_units.len() > 0 {
+                    self.unclaimed_fund_units.insert(
+                        account.address(),
+                        UnclaimedFundUnits {
+                            amounts: unclaimed_fund_units,
+                        }
+                    );
+                }
 
-                // Calculate the value by multiplicating the amounts by the prices
-                // The prices can be taken from the oracle component of from the cache
-                let mut new_protocol_value = match prices.get(&defi_protocol.coin) {
-                    Some(coin_price) => *coin_price * coin_amount,
-                    None => {
-                        let coin_price = self.oracle_component.unwrap().get_price(
-                            defi_protocol.coin,
-                            morpher_data.clone()
-                        );
+                if reclaimed_amount == Decimal::ZERO {
+                    continue;
+                }
 
-                        prices.insert(defi_protocol.coin, coin_price);
+                // Recover the fund units from the AccountLocker and burn or roll them over
+                let fund_units_bucket = FungibleBucket(
+                    self.distribution_account_locker.recover(*account, fund_unit, reclaimed_amount)
+                );
+                if roll_over {
+                    self.rolled_over_fund_units_vault.put(fund_units_bucket);
+                } else {
+                    fund_units_bucket.burn();
+                }
 
-                        coin_price * coin_amount
-                    },
-                };
-                if defi_protocol.other_coin.is_some() {
-                    new_protocol_value += match prices.get(&defi_protocol.other_coin.unwrap()) {
-                        Some(other_coin_price) => *other_coin_price * other_coin_amount.unwrap(),
-                        None => {
-                            let other_coin_price = self.oracle_component.unwrap().get_price(
-                                defi_protocol.other_coin.unwrap(),
-                                morpher_data.clone()
-                            );
+                // Emit the UnclaimedFundUnitsReclaimedEvent event
+                Runtime::emit_event(
+                    UnclaimedFundUnitsReclaimedEvent {
+                        account: account.address(),
+                        amounts: reclaimed_amounts,
+                        rolled_over: roll_over,
+                    }
+                );
+            }
+        }
 
-                            prices.insert(defi_protocol.other_coin.unwrap(), other_coin_price);
+        // Returns the fund units that each distribution plan left in the AccountLocker and that
+        // have not been reclaimed, with the time they were first found there.
+        // Fund units claimed by the stakers are only accounted for when the bot tries to reclaim
+        // them, so the amounts are an upper bound.
+        pub fn unclaimed_fund_units(&self) -> IndexMap<
+            u64,        // Distribution plan id
+            (
+                i64,        // Seconds since Unix epoch
+                Decimal,    // Fund units
+            )
+        > {
+            let mut unclaimed_fund_units = IndexMap::new();
 
-                            other_coin_price * other_coin_amount.unwrap()
-                        },
-                    };
-                }
+            for distribution_id in self.unclaimed_distribution_ids.iter() {
+                let unclaimed_distribution = self.unclaimed_distributions.get(distribution_id).unwrap();
 
-                // Update information about protocol value
-                value_change += new_protocol_value - defi_protocol.value;
-                defi_protocol.value = new_protocol_value;
+                unclaimed_fund_units.insert(
+                    *distribution_id,
+                    (unclaimed_distribution.timestamp, unclaimed_distribution.amount)
+                );
+            }
 
-                // Emit an event for each updated protocol (only the last one will report the new
-                // correct total_value)
-                Runtime::emit_event(
-                    ProtocolValueUpdateEvent {
-                        defi_protocol_name: name.clone(),
-                        protocol_value: new_protocol_value,
-                        total_value: self.total_value + value_change,
-                    }
+            unclaimed_fund_units
+        }
+
+        // Private method to compute the amount of fund units a vesting receipt has released so far
+        // and not claimed yet
+        fn claimable_amount(
+            vesting_receipt: &VestingReceipt,
+            now: i64,
+        ) -> Decimal {
+            let vested_amount = if now >= vesting_receipt.end {
+                vesting_receipt.fund_unit_amount
+            } else if now <= vesting_receipt.start {
+                Decimal::ZERO
+            } else {
+                vesting_receipt.fund_unit_amount * (now - vesting_receipt.start) /
+                    (vesting_receipt.end - vesting_receipt.start)
+            };
+
+            vested_amount - vesting_receipt.claimed_amount
+        }
+
+        // The owner of one or more vesting receipts can invoke this method to get the fund units
+        // they have released so far
+        pub fn claim_vested(
+            &mut self,
+            vesting_receipts_proof: Proof,
+        ) -> FungibleBucket {
+            assert!(
+                !self.withdrawals_paused || self.liquidating,
+                "Withdrawals paused",
+            );
+
+            let vesting_receipts = vesting_receipts_proof.check_with_message(
+                self.vesting_receipt_resource_manager.address(),
+                "Incorrect proof",
+            )
+                .as_non_fungible()
+                .non_fungibles::<VestingReceipt>();
+
+            // During the liquidation everything is released so that it can be redeemed
+            let now = match self.liquidating {
+                true => i64::MAX,
+                false => Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch,
+            };
+            let mut vesting_receipt_ids: Vec<NonFungibleLocalId> = vec![];
+            let mut fund_unit_amount = Decimal::ZERO;
+            for vesting_receipt in vesting_receipts.iter() {
+                let data = vesting_receipt.data();
+                let amount = Self::claimable_amount(&data, now);
+                if amount == Decimal::ZERO {
+                    continue;
+                }
+
+                self.vesting_receipt_resource_manager.update_non_fungible_data(
+                    vesting_receipt.local_id(),
+                    "claimed_amount",
+                    data.claimed_amount + amount,
                 );
+                vesting_receipt_ids.push(vesting_receipt.local_id().clone());
+                fund_unit_amount += amount;
             }
 
-            // Update information about fund value
-            self.total_value += value_change;
+            // Emit the VestedFundUnitsClaimedEvent event
+            Runtime::emit_event(
+                VestedFundUnitsClaimedEvent {
+                    vesting_receipt_ids: vesting_receipt_ids,
+                    fund_unit_amount: fund_unit_amount,
+                }
+            );
+
+            self.vesting_vault.take(fund_unit_amount)
         }
 
-        // Set the desired percentage for one of more DeFi protocol positions.
-        // The method doesn't actually move any funds; it only influences the future deposit and
-        // withdraws.
-        // FundManager doesn't check that the sum of the percentages is 100; each percentage 
-        // should be considered as a share of the sum of the percentages.
-        pub fn set_defi_protocols_percentage(
+        // Returns the amount of fund units a vesting receipt has released so far and not claimed
+        // yet and the amount still locked
+        pub fn vested_amount(
+            &self,
+            vesting_receipt_id: u64,
+        ) -> (Decimal, Decimal) {
+            let vesting_receipt = self.vesting_receipt_resource_manager.get_non_fungible_data::<VestingReceipt>(
+                &NonFungibleLocalId::integer(vesting_receipt_id)
+            );
+            let now = match self.liquidating {
+                true => i64::MAX,
+                false => Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch,
+            };
+            let claimable_amount = Self::claimable_amount(&vesting_receipt, now);
+
+            (
+                claimable_amount,
+                vesting_receipt.fund_unit_amount - vesting_receipt.claimed_amount - claimable_amount,
+            )
+        }
+
+        // As an alternative to fund_units_distribution, the bot can invoke this method to commit
+        // the Merkle root of the (account, share) snapshot of the recently minted fund units.
+        // The fund units are moved to a new distribution and each staker, or anyone on their
+        // behalf, can claim them with a Merkle proof through claim_fund_units.
+        pub fn commit_distribution_merkle_root(
             &mut self,
-            defi_protocols_desired_percentage: HashMap<String, u8>,
+            merkle_root: Hash,
+        ) -> u64 {
+            assert!(
+                !self.bot_operations_paused,
+                "Bot operations paused",
+            );
+            assert!(
+                self.fund_units_vault.amount() > Decimal::ZERO,
+                "Nothing to distribute",
+            );
+            assert!(
+                self.distribution_plan.is_none() ||
+                self.distribution_plan.as_ref().unwrap().paid_stakers == 0,
+                "Distribution in progress",
+            );
+            self.distribution_plan = None;
+
+            // Move exactly the fund units to distribute to the new distribution and burn the
+            // remainings
+            self.last_merkle_distribution_id += 1;
+            self.merkle_distributions.insert(
+                self.last_merkle_distribution_id,
+                MerkleDistribution {
+                    merkle_root: merkle_root,
+                    fund_unit_amount: self.fund_units_to_distribute,
+                    vault: FungibleVault::with_bucket(
+                        self.fund_units_vault.take(self.fund_units_to_distribute)
+                    ),
+                    timestamp: Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch,
+                    swept: false,
+                }
+            );
+            self.fund_units_vault.take_all().burn();
+
+            // Emit the MerkleRootCommittedEvent event
+            Runtime::emit_event(
+                MerkleRootCommittedEvent {
+                    distribution_id: self.last_merkle_distribution_id,
+                    merkle_root: merkle_root,
+                    fund_unit_amount: self.fund_units_to_distribute,
+                }
+            );
+
+            self.last_merkle_distribution_id
+        }
+
+        // Anyone can invoke this method to send an account its share of a Merkle distribution.
+        // The leaf is the hash of (distribution_id, account, share); each leaf can be claimed
+        // only once.
+        pub fn claim_fund_units(
+            &mut self,
+            distribution_id: u64,
+            account: Global<Account>,
+            share: Decimal,             // Share of the distributed fund units (0-1)
+            merkle_proof: Vec<Hash>,    // Sibling hashes from the leaf up to the root
         ) {
+            assert!(
+                !self.withdrawals_paused || self.liquidating,
+                "Withdrawals paused",
+            );
 
-            // For each position to update
-            for (name, percentage) in defi_protocols_desired_percentage.iter() {
+            let leaf = merkle_leaf(distribution_id, account, share);
+            assert!(
+                self.merkle_claimed_leaves.get(&leaf).is_none(),
+                "Already claimed",
+            );
 
-                // Check that the percentage is acceptable
-                assert!(
-                    *percentage <= 100,
-                    "Pergentage out of the 0-100 range"
-                );
+            let mut distribution = self.merkle_distributions.get_mut(&distribution_id)
+                .expect("Distribution not found");
+            assert!(
+                !distribution.swept,
+                "Distribution swept",
+            );
+            assert!(
+                verify_merkle_proof(distribution.merkle_root, leaf, &merkle_proof),
+                "Invalid proof",
+            );
 
-                // Update the DeFi protocol information
-                let mut defi_protocol = self.defi_protocols.get_mut(&name).expect("Not found");
-                defi_protocol.desired_percentage = *percentage;
-            }
+            // Take the fund units out of the distribution vault; the last claim can't take more
+            // than what is left because of rounding
+            let fund_unit_amount = (share * distribution.fund_unit_amount).min(distribution.vault.amount());
+            let fund_units_bucket = distribution.vault.take(fund_unit_amount);
+            drop(distribution);
+
+            self.merkle_claimed_leaves.insert(leaf, distribution_id);
+
+            // Fund units refused by the account stay in the AccountLocker
+            self.account_locker.store(
+                account,
+                fund_units_bucket.into(),
+                true,
+            );
+
+            // Emit the FundUnitsClaimedEvent event
+            Runtime::emit_event(
+                FundUnitsClaimedEvent {
+                    distribution_id: distribution_id,
+                    account: account.address(),
+                    share: share,
+                    fund_unit_amount: fund_unit_amount,
+                }
+            );
         }
 
-        // Private method to select the DeFi protocol position to withdraw the given USD value from
-        fn find_where_to_withdraw_from(
+        // Returns the Merkle root, the amount of distributed fund units and the amount not
+        // claimed yet of a Merkle distribution
+        pub fn merkle_distribution(
             &self,
-            amount: Decimal,    // USD value to withdraw
-        ) -> (
-            String,             // Name of the DeFi protocol position
-            Decimal,            // USD value that can actually be withdrawn
+            distribution_id: u64,
+        ) -> (Hash, Decimal, Decimal) {
+            let distribution = self.merkle_distributions.get(&distribution_id)
+                .expect("Distribution not found");
+
+            (
+                distribution.merkle_root,
+                distribution.fund_unit_amount,
+                distribution.vault.amount(),
+            )
+        }
+
+        // Tells whether the share of an account in a Merkle distribution has already been
+        // claimed
+        pub fn fund_units_claimed(
+            &self,
+            distribution_id: u64,
+            account: Global<Account>,
+            share: Decimal,
+        ) -> bool {
+            self.merkle_claimed_leaves.get(&merkle_leaf(distribution_id, account, share)).is_some()
+        }
+
+        // The bot can invoke this method to sweep the fund units of a Merkle distribution that
+        // have not been claimed within unclaimed_fund_units_expiry; no further claims are
+        // accepted.
+        // The swept fund units are burnt or, if roll_over is true, added to the next
+        // distribution.
+        pub fn sweep_merkle_distribution(
+            &mut self,
+            distribution_id: u64,
+            roll_over: bool,
         ) {
-            // Create a list of DeFi protocols whose value is not less than amount
-            let mut defi_protocol_candidates: Vec<String> = vec![];
-            for name in self.defi_protocols_list.iter() {
-                let value = self.defi_protocols.get(&name).unwrap().value;
+            assert!(
+                !self.bot_operations_paused,
+                "Bot operations paused",
+            );
 
-                if value >= amount {
-                    defi_protocol_candidates.push(name.to_string());
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+            let mut distribution = self.merkle_distributions.get_mut(&distribution_id)
+                .expect("Distribution not found");
+            assert!(
+                !distribution.swept,
+                "Distribution swept",
+            );
+            assert!(
+                distribution.timestamp + self.unclaimed_fund_units_expiry <= now,
+                "Distribution not expired",
+            );
+            distribution.swept = true;
+
+            // Burn or roll over the unclaimed fund units
+            let fund_unit_amount = distribution.vault.amount();
+            let fund_units_bucket = distribution.vault.take_all();
+            drop(distribution);
+            if roll_over {
+                self.rolled_over_fund_units_vault.put(fund_units_bucket);
+            } else {
+                fund_units_bucket.burn();
+            }
+
+            // Emit the MerkleDistributionSweptEvent event
+            Runtime::emit_event(
+                MerkleDistributionSweptEvent {
+                    distribution_id: distribution_id,
+                    fund_unit_amount: fund_unit_amount,
+                    rolled_over: roll_over,
                 }
+            );
+        }
+
+        // Register the wrapper for a DeFi protocol and assign a name to it.
+        // If a wrapper with the same name is already registered, this method removes protocol
+        // tokens from the old wrapper and deposits them in the new one.
+        // This operation requires authorization from the other admins; they have to agree on the
+        // wrapper name too.
+        pub fn add_defi_protocol(
+            &mut self,
+            admin_proof: Proof,
+            name: String, // The name to assign to the protocol wrapper
+            coin: ResourceAddress, // The main coin managed by the new protocol
+            protocol_token: ResourceAddress, // The token belonging to this DeFi protocol
+            other_coin: Option<ResourceAddress>, // Eventual other coin managed by the protocol
+            desired_percentage: u8, // The percentage of the fund value that we want to be
+                                    // deposited in this protocol
+            wrapper: DefiProtocolInterfaceScryptoStub, // Component address of the wrapper
+            needed_morpher_data: Option<ResourceAddress>, // Whether the protocol needs data from
+                                                          // the Morpher oracle
+        ) {
+
+            // Check that there are enough authorizations for this operation.
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::AddDefiProtocol,
+                arguments_hash(&(
+                    name.clone(),
+                    coin,
+                    protocol_token,
+                    other_coin,
+                    desired_percentage,
+                    wrapper,
+                    needed_morpher_data,
+                )),
+            ) {
+                return;
             }
 
-            // If the list is empty return the DeFi protocol with the largest USD value an its USD
-            // value
-            if defi_protocol_candidates.len() == 0 {
-                let mut largest_value = Decimal::ZERO;
-                let mut largest_value_name: Option<String> = None;
+            assert!(
+                !self.liquidating,
+                "Fund in liquidation",
+            );
 
-                for name in self.defi_protocols_list.iter() {
-                    let value = self.defi_protocols.get(&name).unwrap().value;
+            let mut old_defi_protocol: Option<DefiProtocol> = None;
 
-                    if value > largest_value {
-                        largest_value = value;
-                        largest_value_name = Some(name.to_string());
-                    }
-                }
+            // If there's no wrapper with the same name check that the list isn't getting too big
+            // then add the name to the list
+            if self.defi_protocols_list.iter().position(|n| *n == name).is_none() {
+                assert!(
+                    self.defi_protocols_list.len() < MAX_VECTOR_SIZE,
+                    "Protocols list is getting too big",
+                );
 
-                return (largest_value_name.unwrap(), largest_value);
+                self.defi_protocols_list.push(name.clone());
+
+            // Otherwise get info about the existing wrapper position
+            } else {
+                old_defi_protocol= self.defi_protocols.remove(&name);
             }
 
-            // Search in the list of candidate protocols the one whose value percentage is bigger
-            // than the desidered one
-            let mut largest_percentage_diff: Decimal = dec!(-101);
-            let mut largest_percentage_diff_name: Option<String> = None;
-            for name in defi_protocol_candidates.iter() {
-                let defi_protocol = self.defi_protocols.get(&name).unwrap();
-                let percentage = 100 * defi_protocol.value / self.total_value;
-                let percentage_diff: Decimal = percentage - defi_protocol.desired_percentage;
+            // Create the new protocol wrapper position
+            let mut new_defi_protocol = DefiProtocol {
+                value: Decimal::ZERO,
+                last_update: Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch,
+                desired_percentage: desired_percentage,
+                wrapper: wrapper,
+                coin: coin,
+                protocol_token: protocol_token,
+                other_coin: other_coin,
+                needed_morpher_data: needed_morpher_data,
+                max_value: None,
+                max_percentage: None,
+            };
+
+            // Get liquidity from the old protocol wrapper position and deposit it in the new one
+            if old_defi_protocol.is_some() {
+                new_defi_protocol.value = old_defi_protocol.as_ref().unwrap().value;
+                new_defi_protocol.last_update = old_defi_protocol.as_ref().unwrap().last_update;
+                new_defi_protocol.max_value = old_defi_protocol.as_ref().unwrap().max_value;
+                new_defi_protocol.max_percentage = old_defi_protocol.as_ref().unwrap().max_percentage;
+
+                self.fund_manager_badge_vault.authorize_with_amount(
+                    1,
+                    || {
+                        let (token_bucket, coin_bucket, other_coin_bucket) =
+                            old_defi_protocol.unwrap().wrapper.withdraw_all();
+
+                        new_defi_protocol.wrapper.deposit_all(
+                            token_bucket,
+                            coin_bucket,
+                            other_coin_bucket,
+                        );
+                    }
+                );
+            }
+
+            // Add the new DeFi protocol wrapper position in the KeyValueStore
+            self.defi_protocols.insert(
+                name,
+                new_defi_protocol,
+            );
+        }
+
+        // An admin can invoke this method to deposit coins in an existing DeFi protocol and
+        // eventually mint new fund units corresponding to the value of the added coins.
+        // There's no need for authorization; a single admin can invoke this method.
+        pub fn deposit_coin(
+            &mut self,
+            defi_protocol_name: String, // The name of the protocol to deposit the coin in
+            coin_bucket: FungibleBucket, // Bucket of coin to deposit
+            other_coin_bucket: Option<FungibleBucket>, // Eventual additional bucket of coin
+
+            // Eventual Morpher data required by the protocol or the oracle component
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+
+            mint_fund_units: bool, // Whether to mint new fund units whose value is equal to the
+                                   // one of the deposited coins
+        ) -> Option<FungibleBucket> // Fund units
+        {
+            assert!(
+                !mint_fund_units || !self.deposits_paused,
+                "Deposits paused",
+            );
+            assert!(
+                !mint_fund_units || !self.liquidating,
+                "Fund in liquidation",
+            );
+
+            // Compute the USD value of the first bucket of deposited coins
+            let coin_price = self.oracle_component.unwrap().get_price(
+                coin_bucket.resource_address(),
+                morpher_data.clone(),
+            );
+            let mut buckets_value = coin_bucket.amount() * coin_price;
+
+            // Get the current value of a fund unit, refreshing obsolete DeFi protocol values first
+            // if new fund units have to be minted
+            let fund_unit_gross_value = match mint_fund_units {
+                true => {
+                    self.refresh_stale_defi_protocols_value(morpher_data.clone());
+
+                    self.fund_unit_mint_value()
+                },
+                false => Decimal::ZERO,
+            };
+
+            // Get information about the DeFi protocol to deposit the buckets in
+            let mut defi_protocol = self.defi_protocols.get_mut(&defi_protocol_name).expect("Protocol not found");
+
+            // Extract the Morpher data needed by the DeFi protocol from the ones received
+            let (message, signature) = match defi_protocol.needed_morpher_data {
+                Some(resource_address) => {
+                    let morpher_data_needed_by_protocol = morpher_data.get(&resource_address).expect("Missing needed morpher data").clone();
+                    
+                    (Some(morpher_data_needed_by_protocol.0), Some(morpher_data_needed_by_protocol.1))
+                },
+                None => (None, None),
+            };
+
+            // If the protocol handles two coins update buckets_value so that it contains the USD
+            // value of both buckets
+            let other_coin_price = match defi_protocol.other_coin {
+                None => None,
+                Some(other_coin) => {
+                    let other_coin_price = self.oracle_component.unwrap().get_price(
+                        other_coin,
+                        morpher_data
+                    );
+
+                    if other_coin_bucket.is_some() {
+                        buckets_value += other_coin_bucket.as_ref().unwrap().amount() * other_coin_price;
+                    }
+
+                    Some(other_coin_price)
+                },
+            };
+
+            // Deposit the buckets in the DeFi protocol and get the total number of coins invested
+            let (coin_amount, other_coin_amount) = self.fund_manager_badge_vault.authorize_with_amount(
+                1,
+                || defi_protocol.wrapper.deposit_coin(
+                    coin_bucket,
+                    other_coin_bucket,
+                    message,
+                    signature,
+                )
+            );
+
+            // Compute the new value of the DeFi protocol position and update cached values 
+            // information accordingly
+            let mut new_protocol_value = coin_amount * coin_price;
+            if other_coin_amount.is_some() {
+                new_protocol_value += other_coin_amount.unwrap() * other_coin_price.unwrap();
+            }
+            self.total_value += new_protocol_value - defi_protocol.value;
+            defi_protocol.value = new_protocol_value;
+            defi_protocol.last_update = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+            // Make sure the deposit doesn't push the position above its caps
+            assert!(
+                Self::headroom(&defi_protocol, self.total_value) >= Decimal::ZERO,
+                "Cap exceeded",
+            );
+
+            // Emit the AdminDepositEvent event
+            Runtime::emit_event(
+                AdminDepositEvent {
+                    defi_protocol_name: defi_protocol_name,
+                    protocol_value: new_protocol_value,
+                    total_value: self.total_value,
+                }
+            );
+
+            drop(defi_protocol);
+
+            // Mint the new fund units if required
+            let fund_units_bucket = if mint_fund_units {
+                Some(self.fund_unit_resource_manager.mint(buckets_value / fund_unit_gross_value))
+            } else {
+                None
+            };
+            self.record_nav_checkpoint();
+
+            fund_units_bucket
+        }
+
+        // An admin can invoke this method to deposit protocol tokens in an existing DeFi protocol
+        // and eventually mint new fund units corresponding to the value of the added tokens.
+        // There's no need for authorization; a single admin can invoke this method.
+        pub fn deposit_protocol_token(
+            &mut self,
+            defi_protocol_name: String, // The name of the protocol to deposit the tokens in
+            protocol_token_bucket: Bucket, // The bicket of tokens to deposit
+
+            // Eventual Morpher data required by the protocol or the oracle component
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+
+            mint_fund_units: bool, // Whether to mint new fund units or not
+        ) -> Option<FungibleBucket> // Fund units
+        {
+            assert!(
+                !mint_fund_units || !self.deposits_paused,
+                "Deposits paused",
+            );
+            assert!(
+                !mint_fund_units || !self.liquidating,
+                "Fund in liquidation",
+            );
+
+            // Refresh obsolete DeFi protocol values if new fund units have to be minted
+            if mint_fund_units {
+                self.refresh_stale_defi_protocols_value(morpher_data.clone());
+            }
+
+            // Get information about the DeFi protocol to deposit the bucket in
+            let mut defi_protocol = self.defi_protocols.get_mut(&defi_protocol_name).unwrap();
+
+            // Get the current amount of coins invested in this DeFi protocol position
+            let (old_coin_amount, old_other_coin_amount) = defi_protocol.wrapper.get_coin_amounts();
+
+            // Deposit the tokens and get the updated number of coins
+            let (coin_amount, other_coin_amount) = self.fund_manager_badge_vault.authorize_with_amount(
+                1,
+                || defi_protocol.wrapper.deposit_all(protocol_token_bucket, None, None)
+            );
+
+            // Compute the protocol value immediately before and after the deposit
+            let coin_price = self.oracle_component.unwrap().get_price(
+                defi_protocol.coin,
+                morpher_data.clone()
+            );
+            let mut protocol_value = coin_price * old_coin_amount;
+            let mut new_protocol_value = coin_price * coin_amount;
+            if defi_protocol.other_coin.is_some() {
+                let other_coin_price = self.oracle_component.unwrap().get_price(
+                    defi_protocol.other_coin.unwrap(),
+                    morpher_data
+                );
+
+                protocol_value += other_coin_price * old_other_coin_amount.unwrap();
+                new_protocol_value += other_coin_price * other_coin_amount.unwrap();
+            }
+
+            // Update cached value information
+            self.total_value += new_protocol_value - defi_protocol.value;
+            defi_protocol.value = new_protocol_value;
+            defi_protocol.last_update = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+            // Make sure the deposit doesn't push the position above its caps
+            assert!(
+                Self::headroom(&defi_protocol, self.total_value) >= Decimal::ZERO,
+                "Cap exceeded",
+            );
+
+            // Emit the AdminDepositEvent event
+            Runtime::emit_event(
+                AdminDepositEvent {
+                    defi_protocol_name: defi_protocol_name,
+                    protocol_value: new_protocol_value,
+                    total_value: self.total_value,
+                }
+            );
+
+            drop(defi_protocol);
+
+            // If requested, mint new fund units corresponding to the increase value because of
+            // the deposit
+            let fund_units_bucket = match mint_fund_units {
+                false => None,
+                true => {
+                    let fund_unit_gross_value = self.fund_unit_mint_value();
+
+                    Some(
+                        self.fund_unit_resource_manager.mint(
+                            (new_protocol_value - protocol_value) / fund_unit_gross_value
+                        )
+                    )
+                },
+            };
+            self.record_nav_checkpoint();
+
+            fund_units_bucket
+        }
+
+        // Removes a DeFi protocol wrapper and returns the badge to control the Account used by the
+        // wrapper
+        // An admin can perform this operation only when authorized by the other admins
+        pub fn remove_defi_protocol(
+            &mut self,
+            admin_proof: Proof,
+            name: String,
+        ) -> Option<NonFungibleBucket> {
+
+            // Check other admins' authorizations
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::RemoveDefiProtocol,
+                arguments_hash(&(name.clone(),)),
+            ) {
+                return None;
+            }
+
+            // Remove the protocol position from the Vector and the KeyValueStore
+            self.defi_protocols_list.retain(|n| { *n != name });
+            let mut defi_protocol = self.defi_protocols.remove(&name)
+                .expect("Protocol not found");
+
+            // Update the total fund value and emit a RemovedProtocolEvent event containing this
+            // information
+            self.total_value -= defi_protocol.value;
+            Runtime::emit_event(
+                RemovedProtocolEvent {
+                    defi_protocol_name: name,
+                    total_value: self.total_value,
+                }
+            );
+            self.record_nav_checkpoint();
+
+            // Get the Account badge
+            Some(
+                self.fund_manager_badge_vault.authorize_with_amount(
+                    1,
+                    || defi_protocol.wrapper.withdraw_account_badge()
+                )
+            )
+        }
+
+        // Updates the cached value of the specified DeFi protocols by asking amounts to the
+        // protocols themselves and prices to the oracle component
+        pub fn update_defi_protocols_value(
+            &mut self,
+            defi_protocols: IndexSet<String>,
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+        ) {
+            assert!(
+                !self.bot_operations_paused,
+                "Bot operations paused",
+            );
+
+            self.accrue_management_fee();
+
+            // If a DeFi protocol position value dropped too much, pause everything and stop here
+            let tripping_defi_protocol = self.refresh_defi_protocols_value(defi_protocols, morpher_data);
+            if tripping_defi_protocol.is_some() {
+                self.withdrawals_paused = true;
+                self.deposits_paused = true;
+                self.bot_operations_paused = true;
+
+                // Emit the PauseStateEvent event
+                Runtime::emit_event(
+                    PauseStateEvent {
+                        withdrawals_paused: true,
+                        deposits_paused: true,
+                        bot_operations_paused: true,
+                        admin_id: None,
+                        defi_protocol_name: tripping_defi_protocol,
+                    }
+                );
+
+                return;
+            }
+
+            self.crystallize_performance_fee();
+            self.record_nav_checkpoint();
+        }
+
+        // Private method to update the cached value of the DeFi protocol positions whose value is
+        // older than max_value_age.
+        // It is invoked before minting or burning fund units so that their value is not computed
+        // on obsolete information.
+        // If the circuit breaker trips, the whole operation is reverted; the bot can then pause
+        // the fund through update_defi_protocols_value.
+        fn refresh_stale_defi_protocols_value(
+            &mut self,
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+        ) {
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+            let mut stale_defi_protocols: IndexSet<String> = IndexSet::new();
+            for name in self.defi_protocols_list.iter() {
+                if self.defi_protocols.get(&name).unwrap().last_update + self.max_value_age < now {
+                    stale_defi_protocols.insert(name.clone());
+                }
+            }
+
+            if stale_defi_protocols.len() > 0 {
+                assert!(
+                    self.refresh_defi_protocols_value(stale_defi_protocols, morpher_data).is_none(),
+                    "Circuit breaker tripped",
+                );
+            }
+        }
+
+        // Private method to update the cached value of the specified DeFi protocols.
+        // It returns the name of the DeFi protocol position whose value drop tripped the circuit
+        // breaker, if any.
+        fn refresh_defi_protocols_value(
+            &mut self,
+            defi_protocols: IndexSet<String>,
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+        ) -> Option<String> {
+            let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+            // Variable to store the total fund value change
+            let mut value_change = Decimal::ZERO;
+
+            // Coin prices cache
+            let mut prices: HashMap<ResourceAddress, Decimal> = HashMap::new();
+
+            // DeFi protocol position whose value dropped too much, if any
+            let mut tripping_defi_protocol: Option<String> = None;
+
+            // For each protocol
+            for name in defi_protocols.iter() {
+                let mut defi_protocol = self.defi_protocols.get_mut(&name).unwrap();
+
+                // Get the coin amounts
+                let (coin_amount, other_coin_amount) = defi_protocol.wrapper.get_coin_amounts();
+
+                // Calculate the value by multiplicating the amounts by the prices
+                // The prices can be taken from the oracle component of from the cache
+                let mut new_protocol_value = match prices.get(&defi_protocol.coin) {
+                    Some(coin_price) => *coin_price * coin_amount,
+                    None => {
+                        let coin_price = self.oracle_component.unwrap().get_price(
+                            defi_protocol.coin,
+                            morpher_data.clone()
+                        );
+
+                        prices.insert(defi_protocol.coin, coin_price);
+
+                        coin_price * coin_amount
+                    },
+                };
+                if defi_protocol.other_coin.is_some() {
+                    new_protocol_value += match prices.get(&defi_protocol.other_coin.unwrap()) {
+                        Some(other_coin_price) => *other_coin_price * other_coin_amount.unwrap(),
+                        None => {
+                            let other_coin_price = self.oracle_component.unwrap().get_price(
+                                defi_protocol.other_coin.unwrap(),
+                                morpher_data.clone()
+                            );
+
+                            prices.insert(defi_protocol.other_coin.unwrap(), other_coin_price);
+
+                            other_coin_price * other_coin_amount.unwrap()
+                        },
+                    };
+                }
+
+                // Check whether the value drop trips the circuit breaker
+                if self.circuit_breaker_percentage > 0 &&
+                    new_protocol_value < defi_protocol.value * (100 - self.circuit_breaker_percentage) / 100 {
+                    tripping_defi_protocol = Some(name.clone());
+                }
+
+                // Update information about protocol value
+                value_change += new_protocol_value - defi_protocol.value;
+                defi_protocol.value = new_protocol_value;
+                defi_protocol.last_update = now;
+
+                // Emit an event for each updated protocol (only the last one will report the new
+                // correct total_value)
+                Runtime::emit_event(
+                    ProtocolValueUpdateEvent {
+                        defi_protocol_name: name.clone(),
+                        protocol_value: new_protocol_value,
+                        total_value: self.total_value + value_change,
+                    }
+                );
+            }
+
+            // Update information about fund value
+            self.total_value += value_change;
+
+            tripping_defi_protocol
+        }
+
+        // Set the desired percentage for one of more DeFi protocol positions.
+        // The method doesn't actually move any funds; it only influences the future deposit and
+        // withdraws.
+        // FundManager doesn't check that the sum of the percentages is 100; each percentage 
+        // should be considered as a share of the sum of the percentages.
+        pub fn set_defi_protocols_percentage(
+            &mut self,
+            defi_protocols_desired_percentage: HashMap<String, u8>,
+        ) {
+            assert!(
+                !self.bot_operations_paused,
+                "Bot operations paused",
+            );
+
+            // For each position to update
+            for (name, percentage) in defi_protocols_desired_percentage.iter() {
+
+                // Check that the percentage is acceptable
+                assert!(
+                    *percentage <= 100,
+                    "Pergentage out of the 0-100 range"
+                );
+
+                // Update the DeFi protocol information
+                let mut defi_protocol = self.defi_protocols.get_mut(&name).expect("Not found");
+                defi_protocol.desired_percentage = *percentage;
+            }
+        }
+
+        // The bot can call this method to move value from the most overweight DeFi protocol
+        // position to the most underweight one.
+        // The moved value is limited by how much the two positions differ from their desired
+        // percentage and by rebalance_max_percentage.
+        // Coins are swapped through the dex component when the two positions don't share a coin.
+        pub fn rebalance(
+            &mut self,
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+        ) {
+            assert!(
+                !self.bot_operations_paused,
+                "Bot operations paused",
+            );
+
+            self.accrue_management_fee();
+            assert!(
+                self.rebalance_max_percentage > 0,
+                "Rebalance not enabled",
+            );
+
+            // Compute the sum of the desired percentages; each percentage is considered as a share
+            // of this sum
+            let mut total_desired_percentage = Decimal::ZERO;
+            for name in self.defi_protocols_list.iter() {
+                total_desired_percentage += Decimal::from(self.defi_protocols.get(&name).unwrap().desired_percentage);
+            }
+            assert!(
+                total_desired_percentage > Decimal::ZERO,
+                "No desired percentage set",
+            );
+
+            // Find the DeFi protocol positions whose value exceeds the desired one (or its caps)
+            // the most and whose value is the most below the desired one without hitting its caps
+            let mut largest_excess = Decimal::ZERO;
+            let mut largest_excess_name: Option<String> = None;
+            let mut largest_shortfall = Decimal::ZERO;
+            let mut largest_shortfall_name: Option<String> = None;
+            for name in self.defi_protocols_list.iter() {
+                let defi_protocol = self.defi_protocols.get(&name).unwrap();
+
+                let desired_value =
+                    self.total_value * defi_protocol.desired_percentage / total_desired_percentage;
+                let headroom = Self::headroom(&defi_protocol, self.total_value);
+
+                let excess = (defi_protocol.value - desired_value).max(-headroom);
+                let shortfall = (desired_value - defi_protocol.value).min(headroom);
+
+                if excess > largest_excess {
+                    largest_excess = excess;
+                    largest_excess_name = Some(name.to_string());
+                } else if shortfall > largest_shortfall {
+                    largest_shortfall = shortfall;
+                    largest_shortfall_name = Some(name.to_string());
+                }
+            }
+            let from_defi_protocol_name = largest_excess_name.expect("Nothing to rebalance");
+            let to_defi_protocol_name = largest_shortfall_name.expect("Nothing to rebalance");
+
+            // Compute the value to move
+            let value_to_move = largest_excess
+                .min(largest_shortfall)
+                .min(self.total_value * self.rebalance_max_percentage / 100);
+
+            let total_value_before = self.total_value;
+            let from_protocol_value_before = self.defi_protocols.get(&from_defi_protocol_name).unwrap().value;
+            let to_protocol_value_before = self.defi_protocols.get(&to_defi_protocol_name).unwrap().value;
+
+            // Withdraw the coins from the overweight position
+            let (coin_bucket, other_coin_bucket, moved_value, from_protocol_value_after) =
+                self.withdraw_from_defi_protocol(
+                    &from_defi_protocol_name,
+                    value_to_move,
+                    morpher_data.clone(),
+                );
+
+            // Deposit them in the underweight position; the coins it doesn't accept are swapped
+            // for its main coin requiring a minimum output based on the oracle prices
+            let (to_coin, to_other_coin) = {
+                let defi_protocol = self.defi_protocols.get(&to_defi_protocol_name).unwrap();
+
+                (defi_protocol.coin, defi_protocol.other_coin)
+            };
+            let to_coin_price = self.oracle_component.unwrap().get_price(
+                to_coin,
+                morpher_data.clone()
+            );
+            let mut deposited_value = Decimal::ZERO;
+            let mut to_protocol_value_after = to_protocol_value_before;
+            for mut bucket in [Some(coin_bucket), other_coin_bucket].into_iter().flatten() {
+                if bucket.amount() == Decimal::ZERO {
+                    bucket.drop_empty();
+                    continue;
+                }
+
+                let bucket_value = bucket.amount() * self.oracle_component.unwrap().get_price(
+                    bucket.resource_address(),
+                    morpher_data.clone()
+                );
+                if bucket.resource_address() == to_coin || Some(bucket.resource_address()) == to_other_coin {
+                    deposited_value += bucket_value;
+                } else {
+                    bucket = self.swap_with_min_output(
+                        bucket,
+                        to_coin,
+                        bucket_value * (1 - ACCEPTABLE_VALUE_DIFFERENCE) / to_coin_price,
+                    );
+                    deposited_value += bucket.amount() * to_coin_price;
+                }
+
+                to_protocol_value_after = self.deposit_in_defi_protocol(
+                    &to_defi_protocol_name,
+                    bucket,
+                    morpher_data.clone(),
+                );
+            }
+
+            // Make sure that not too much of the moved value has been lost in the swaps
+            assert!(
+                deposited_value >= moved_value * (1 - ACCEPTABLE_VALUE_DIFFERENCE),
+                "Too much value lost",
+            );
+
+            // Emit the RebalanceEvent event
+            Runtime::emit_event(
+                RebalanceEvent {
+                    from_defi_protocol_name: from_defi_protocol_name,
+                    to_defi_protocol_name: to_defi_protocol_name,
+                    moved_value: moved_value,
+                    from_protocol_value_before: from_protocol_value_before,
+                    from_protocol_value_after: from_protocol_value_after,
+                    to_protocol_value_before: to_protocol_value_before,
+                    to_protocol_value_after: to_protocol_value_after,
+                    total_value_before: total_value_before,
+                    total_value_after: self.total_value,
+                }
+            );
+
+            self.record_nav_checkpoint();
+        }
+
+        // Private method to select the DeFi protocol position to withdraw the given USD value from
+        fn find_where_to_withdraw_from(
+            &self,
+            amount: Decimal,    // USD value to withdraw
+        ) -> (
+            String,             // Name of the DeFi protocol position
+            Decimal,            // USD value that can actually be withdrawn
+        ) {
+            // Use the first position suggested by the allocation strategy component, if set
+            if self.allocation_strategy.is_some() {
+                return self.allocation_strategy_withdrawal_plan(amount).swap_remove(0);
+            }
+
+            // Create a list of DeFi protocols whose value is not less than amount
+            let mut defi_protocol_candidates: Vec<String> = vec![];
+            for name in self.defi_protocols_list.iter() {
+                let value = self.defi_protocols.get(&name).unwrap().value;
+
+                if value >= amount {
+                    defi_protocol_candidates.push(name.to_string());
+                }
+            }
+
+            // If the list is empty return the DeFi protocol with the largest USD value an its USD
+            // value
+            if defi_protocol_candidates.len() == 0 {
+                let mut largest_value = Decimal::ZERO;
+                let mut largest_value_name: Option<String> = None;
+
+                for name in self.defi_protocols_list.iter() {
+                    let value = self.defi_protocols.get(&name).unwrap().value;
+
+                    if value > largest_value {
+                        largest_value = value;
+                        largest_value_name = Some(name.to_string());
+                    }
+                }
+
+                return (largest_value_name.unwrap(), largest_value);
+            }
+
+            // Search in the list of candidate protocols the one whose value percentage is bigger
+            // than the desidered one
+            let mut largest_percentage_diff: Decimal = dec!(-101);
+            let mut largest_percentage_diff_name: Option<String> = None;
+            for name in defi_protocol_candidates.iter() {
+                let defi_protocol = self.defi_protocols.get(&name).unwrap();
+                let percentage = 100 * defi_protocol.value / self.total_value;
+                let percentage_diff: Decimal = percentage - defi_protocol.desired_percentage;
+
+                if percentage_diff > largest_percentage_diff {
+                    largest_percentage_diff = percentage_diff;
+                    largest_percentage_diff_name = Some(name.to_string());
+                }
+            }
+            return (largest_percentage_diff_name.unwrap(), amount);
+        }
+
+        // Private method to plan a withdrawal of the given USD value from multiple DeFi protocol
+        // positions.
+        // Positions are sorted by how much their invested percentage of the fund exceeds the
+        // desired one; the most overweight positions are emptied first.
+        fn find_where_to_withdraw_from_multi(
+            &self,
+            amount: Decimal,    // USD value to withdraw
+        ) -> Vec<(
+            String,             // Name of the DeFi protocol position
+            Decimal,            // USD value to withdraw from this position
+        )> {
+            // Use the allocation strategy component, if set
+            if self.allocation_strategy.is_some() {
+                return self.allocation_strategy_withdrawal_plan(amount);
+            }
+
+            // Create a list of the DeFi protocol positions having some value and compute how much
+            // each one of them is overweight
+            let mut defi_protocol_candidates: Vec<(String, Decimal, Decimal)> = vec![];
+            for name in self.defi_protocols_list.iter() {
+                let defi_protocol = self.defi_protocols.get(&name).unwrap();
+
+                if defi_protocol.value > Decimal::ZERO {
+                    let percentage = 100 * defi_protocol.value / self.total_value;
+                    let percentage_diff: Decimal = percentage - defi_protocol.desired_percentage;
+
+                    defi_protocol_candidates.push(
+                        (name.to_string(), defi_protocol.value, percentage_diff)
+                    );
+                }
+            }
+
+            // Sort the positions from the most overweight to the most underweight
+            defi_protocol_candidates.sort_by(|a, b| b.2.cmp(&a.2));
+
+            // Take from each position as much as possible until amount is reached
+            let mut remaining_amount = amount;
+            let mut withdrawal_plan: Vec<(String, Decimal)> = vec![];
+            for (name, value, _) in defi_protocol_candidates.into_iter() {
+                if remaining_amount <= Decimal::ZERO {
+                    break;
+                }
+
+                let withdrawable_value = value.min(remaining_amount);
+                remaining_amount -= withdrawable_value;
+                withdrawal_plan.push((name, withdrawable_value));
+            }
+
+            withdrawal_plan
+        }
+
+        // Private method to withdraw the given USD value from a DeFi protocol position and update
+        // the cached values of the position and of the whole fund
+        fn withdraw_from_defi_protocol(
+            &mut self,
+            defi_protocol_name: &String,    // Name of the DeFi protocol position
+            withdrawable_value: Decimal,    // USD value to withdraw
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+        ) -> (
+            FungibleBucket,         // coin
+            Option<FungibleBucket>, // other coin
+            Decimal,                // USD value of the returned buckets
+            Decimal,                // New USD value of the DeFi protocol position
+        ) {
+            let mut defi_protocol = self.defi_protocols.get_mut(defi_protocol_name).unwrap();
+
+            // Get the price of the main coin managed from the choosen protocol
+            let coin_price = self.oracle_component.unwrap().get_price(
+                defi_protocol.coin,
+                morpher_data.clone()
+            );
+
+            // Compute the relative price of the two coins of the protocol
+            let (other_coin_to_coin_price_ratio, other_coin_price) = match defi_protocol.other_coin {
+                Some(other_coin) => {
+                    let other_coin_price = self.oracle_component.unwrap().get_price(
+                        other_coin,
+                        morpher_data
+                    );
+
+                    (Some(other_coin_price / coin_price), Some(other_coin_price))
+                },
+                None => (None, None),
+            };
+
+            // Withdraw coins from the protocol position
+            let (coin_bucket, other_coin_bucket, coin_amount, other_coin_amount) = 
+                self.fund_manager_badge_vault.authorize_with_amount(
+                    1,
+                    || defi_protocol.wrapper.withdraw_coin(
+                        withdrawable_value / coin_price,
+                        other_coin_to_coin_price_ratio,
+                    )
+                );
+
+            // Compute the total value of the returned buckets
+            let mut coin_bucket_value = coin_bucket.amount() * coin_price;
+            if other_coin_bucket.is_some() {
+                coin_bucket_value += other_coin_bucket.as_ref().unwrap().amount() * other_coin_price.unwrap();
+            }
+
+            // Update the protocol and total fund value
+            let mut new_protocol_value = coin_amount * coin_price;
+            if defi_protocol.other_coin.is_some() {
+                new_protocol_value += other_coin_price.unwrap() * other_coin_amount.unwrap();
+            }
+            self.total_value += new_protocol_value - defi_protocol.value;
+            defi_protocol.value = new_protocol_value;
+            defi_protocol.last_update = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+            (coin_bucket, other_coin_bucket, coin_bucket_value, new_protocol_value)
+        }
+
+        // Private method to swap the coins withdrawn from a DeFi protocol position for the
+        // specified coin; all of the coins are returned in a single bucket
+        fn swap_withdrawn_coins(
+            &self,
+            coin_bucket: FungibleBucket,
+            other_coin_bucket: Option<FungibleBucket>,
+            swap_to: ResourceAddress,
+        ) -> FungibleBucket {
+            let mut swapped_bucket = FungibleBucket::new(swap_to);
+
+            for bucket in [Some(coin_bucket), other_coin_bucket].into_iter().flatten() {
+                if bucket.resource_address() == swap_to {
+                    swapped_bucket.put(bucket);
+                } else if bucket.amount() == Decimal::ZERO {
+                    bucket.drop_empty();
+                } else {
+                    swapped_bucket.put(
+                        self.fund_manager_badge_vault.authorize_with_amount(
+                            1,
+                            || FungibleBucket(
+                                self.dex.unwrap().swap(
+                                    bucket.into(),
+                                    swap_to,
+                                    false
+                                )
+                            )
+                        )
+                    );
+                }
+            }
+
+            swapped_bucket
+        }
+
+        // Private method to swap a bucket of coins through the dex component; the operation fails
+        // if less than min_amount coins are returned
+        fn swap_with_min_output(
+            &self,
+            bucket: FungibleBucket,             // Coins to swap
+            output_resource: ResourceAddress,   // Output coins resource address
+            min_amount: Decimal,                // Minimum amount of output coins
+        ) -> FungibleBucket {
+            let output_bucket = self.fund_manager_badge_vault.authorize_with_amount(
+                1,
+                || FungibleBucket(
+                    self.dex.unwrap().swap(
+                        bucket.into(),
+                        output_resource,
+                        false
+                    )
+                )
+            );
+
+            assert!(
+                output_bucket.amount() >= min_amount,
+                "Swap output below minimum",
+            );
+
+            output_bucket
+        }
+
+        // Private method to compute the amount of fund units to burn given the USD value withdrawn
+        // and the amount of fund units received
+        fn fund_units_to_burn(
+            withdrawn_value: Decimal,
+            fund_unit_gross_value: Decimal,
+            fund_unit_amount: Decimal,
+        ) -> Decimal {
+            let fund_units_to_burn = withdrawn_value / fund_unit_gross_value;
+
+            if fund_units_to_burn > fund_unit_amount {
+                assert!(
+                    fund_units_to_burn < fund_unit_amount * (1 + ACCEPTABLE_VALUE_DIFFERENCE),
+                    "Too much value withdrawn"
+                );
+
+                fund_unit_amount
+            } else if fund_units_to_burn > fund_unit_amount * (1 - ACCEPTABLE_VALUE_DIFFERENCE) {
+                fund_unit_amount
+            } else {
+                fund_units_to_burn
+            }
+        }
+
+        // Swap fund units for any coin managed by a DeFi protocol or for a specific coin.
+        // This method withdraws from a single DeFi protocol position; if the full value can't be
+        // withdrawn from a single position, some fund units will be returned
+        pub fn withdraw(
+            &mut self,
+            mut fund_units_bucket: FungibleBucket,  // Bucket of funds to swap
+            swap_to: Option<ResourceAddress>,       // If set, the returned coins will be swapped
+                                                    // to this coin
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+        ) -> (
+            FungibleBucket, // coin
+            Option<FungibleBucket>, // other coin
+            Option<FungibleBucket>, // fund units
+        ) {
+            assert!(
+                !self.withdrawals_paused,
+                "Withdrawals paused",
+            );
+            self.accrue_management_fee();
+            assert!(
+                fund_units_bucket.resource_address() == self.fund_unit_resource_manager.address(),
+                "Wrong coin",
+            );
+
+
+            // Refresh obsolete DeFi protocol values, then get the value of a fund unit
+            self.refresh_stale_defi_protocols_value(morpher_data.clone());
+            self.crystallize_performance_fee();
+            let (fund_unit_net_value, fund_unit_gross_value) = self.fund_unit_value();
+
+            // Find the DeFi protocol position to withdraw from
+            let fund_unit_amount = fund_units_bucket.amount();
+            let (defi_protocol_name, withdrawable_value) = self.find_where_to_withdraw_from(
+                fund_unit_amount * fund_unit_net_value
+            );
+
+            // Withdraw coins from the protocol position
+            let (mut coin_bucket, mut other_coin_bucket, coin_bucket_value, new_protocol_value) =
+                self.withdraw_from_defi_protocol(
+                    &defi_protocol_name,
+                    withdrawable_value,
+                    morpher_data,
+                );
+
+            // If swap_to was specified, swap both buckets for the specified coin (put everithing
+            // in coin_bucket, leave other_coin_bucket empty)
+            if swap_to.is_some() {
+                if swap_to.unwrap() != coin_bucket.resource_address() {
+                    coin_bucket = self.fund_manager_badge_vault.authorize_with_amount(
+                        1,
+                        || FungibleBucket(
+                            self.dex.unwrap().swap(
+                                coin_bucket.into(),
+                                swap_to.unwrap(),
+                                false
+                            )
+                        )
+                    );
+                }
+                if other_coin_bucket.is_some() &&
+                    swap_to.unwrap() != other_coin_bucket.as_ref().unwrap().resource_address() {
+                    coin_bucket.put(
+                        self.fund_manager_badge_vault.authorize_with_amount(
+                            1,
+                            || FungibleBucket(
+                                self.dex.unwrap().swap(
+                                    other_coin_bucket.unwrap().into(),
+                                    swap_to.unwrap(),
+                                    false
+                                )
+                            )
+                        )
+                    );
+
+                    other_coin_bucket = None;
+                }
+            }
+
+            // Compute the amount of fund units to burn
+            let fund_units_to_burn = Self::fund_units_to_burn(
+                coin_bucket_value,
+                fund_unit_gross_value,
+                fund_unit_amount,
+            );
+
+            // Emit the WithdrawFromFundEvent event
+            Runtime::emit_event(
+                WithdrawFromFundEvent {
+                    fund_unit_amount: fund_units_to_burn,
+                    defi_protocol_name: defi_protocol_name,
+                    protocol_value: new_protocol_value,
+                    total_value: self.total_value,
+                }
+            );
+
+            // Burn the fund units and return all of the buckets to the caller
+            let fund_units_bucket = if fund_units_to_burn < fund_unit_amount {
+                fund_units_bucket.take(fund_units_to_burn).burn();
+
+                Some(fund_units_bucket)
+            } else {
+                fund_units_bucket.burn();
+
+                None
+            };
+            self.record_nav_checkpoint();
+
+            (coin_bucket, other_coin_bucket, fund_units_bucket)
+        }
+
+        // Swap fund units for the coins managed by one or more DeFi protocols or for a specific
+        // coin.
+        // Unlike withdraw, this method keeps withdrawing from further DeFi protocol positions,
+        // starting from the most overweight one, until the full value is covered.
+        // Coins of the same type are returned in the same bucket.
+        pub fn withdraw_multi(
+            &mut self,
+            fund_units_bucket: FungibleBucket,      // Bucket of funds to swap
+            swap_to: Option<ResourceAddress>,       // If set, the returned coins will be swapped
+                                                    // to this coin
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+        ) -> (
+            Vec<FungibleBucket>,    // coins
+            Option<FungibleBucket>, // fund units
+        ) {
+            self.withdraw_from_multiple_defi_protocols(fund_units_bucket, swap_to, morpher_data)
+        }
+
+        // Same as withdraw_multi but the caller can set a minimum amount for each of the returned
+        // coins.
+        // The whole operation fails if the result falls short of any of these limits; this
+        // protects the user from price manipulations during the final swap.
+        pub fn withdraw_with_min_output(
+            &mut self,
+            fund_units_bucket: FungibleBucket,      // Bucket of funds to swap
+            swap_to: Option<ResourceAddress>,       // If set, the returned coins will be swapped
+                                                    // to this coin
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+            min_amounts: HashMap<ResourceAddress, Decimal>, // Minimum amount of each coin; coins
+                                                            // not in the map have no limit
+        ) -> (
+            Vec<FungibleBucket>,    // coins
+            Option<FungibleBucket>, // fund units
+        ) {
+            let (coin_buckets, fund_units_bucket) = self.withdraw_from_multiple_defi_protocols(
+                fund_units_bucket,
+                swap_to,
+                morpher_data,
+            );
+
+            // Check the amount received for each coin with a limit; a missing bucket counts as
+            // zero
+            for (coin, min_amount) in min_amounts.iter() {
+                let amount = match coin_buckets.iter().find(|bucket| bucket.resource_address() == *coin) {
+                    Some(coin_bucket) => coin_bucket.amount(),
+                    None => Decimal::ZERO,
+                };
+
+                assert!(
+                    amount >= *min_amount,
+                    "Output amount below minimum",
+                );
+            }
+
+            (coin_buckets, fund_units_bucket)
+        }
+
+        // Private method to swap fund units for the coins managed by one or more DeFi protocols
+        // or for a specific coin
+        fn withdraw_from_multiple_defi_protocols(
+            &mut self,
+            mut fund_units_bucket: FungibleBucket,  // Bucket of funds to swap
+            swap_to: Option<ResourceAddress>,       // If set, the returned coins will be swapped
+                                                    // to this coin
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+        ) -> (
+            Vec<FungibleBucket>,    // coins
+            Option<FungibleBucket>, // fund units
+        ) {
+            assert!(
+                !self.withdrawals_paused,
+                "Withdrawals paused",
+            );
+            self.accrue_management_fee();
+            assert!(
+                fund_units_bucket.resource_address() == self.fund_unit_resource_manager.address(),
+                "Wrong coin",
+            );
+
+            // Refresh obsolete DeFi protocol values, then get the value of a fund unit
+            self.refresh_stale_defi_protocols_value(morpher_data.clone());
+            self.crystallize_performance_fee();
+            let (fund_unit_net_value, fund_unit_gross_value) = self.fund_unit_value();
+
+            // Find the DeFi protocol positions to withdraw from
+            let fund_unit_amount = fund_units_bucket.amount();
+            let withdrawal_plan = self.find_where_to_withdraw_from_multi(
+                fund_unit_amount * fund_unit_net_value
+            );
+
+            // Withdraw coins from each DeFi protocol position in the plan and group them by
+            // resource address
+            let mut coin_buckets: IndexMap<ResourceAddress, FungibleBucket> = IndexMap::new();
+            let mut withdrawn_value = Decimal::ZERO;
+            let mut withdrawals: Vec<(String, Decimal, Decimal, Decimal)> = vec![];
+            for (defi_protocol_name, withdrawable_value) in withdrawal_plan.into_iter() {
+                let (coin_bucket, other_coin_bucket, coin_bucket_value, new_protocol_value) =
+                    self.withdraw_from_defi_protocol(
+                        &defi_protocol_name,
+                        withdrawable_value,
+                        morpher_data.clone(),
+                    );
+
+                withdrawn_value += coin_bucket_value;
+                withdrawals.push(
+                    (defi_protocol_name, coin_bucket_value, new_protocol_value, self.total_value)
+                );
+
+                // If swap_to was specified, swap both buckets for the specified coin
+                let buckets = match swap_to {
+                    Some(swap_to) => vec![
+                        self.swap_withdrawn_coins(coin_bucket, other_coin_bucket, swap_to)
+                    ],
+                    None => [Some(coin_bucket), other_coin_bucket].into_iter().flatten().collect(),
+                };
+
+                for bucket in buckets.into_iter() {
+                    match coin_buckets.get_mut(&bucket.resource_address()) {
+                        Some(coin_bucket) => coin_bucket.put(bucket),
+                        None => {
+                            coin_buckets.insert(bucket.resource_address(), bucket);
+                        },
+                    }
+                }
+            }
+
+            // Compute the amount of fund units to burn
+            let fund_units_to_burn = Self::fund_units_to_burn(
+                withdrawn_value,
+                fund_unit_gross_value,
+                fund_unit_amount,
+            );
+
+            // Emit a WithdrawFromFundEvent event for each DeFi protocol position; the burnt fund
+            // units are split in proportion to the value withdrawn from each position
+            for (defi_protocol_name, coin_bucket_value, protocol_value, total_value) in withdrawals.into_iter() {
+                let fund_unit_amount = match withdrawn_value > Decimal::ZERO {
+                    true => fund_units_to_burn * coin_bucket_value / withdrawn_value,
+                    false => Decimal::ZERO,
+                };
+
+                Runtime::emit_event(
+                    WithdrawFromFundEvent {
+                        fund_unit_amount: fund_unit_amount,
+                        defi_protocol_name: defi_protocol_name,
+                        protocol_value: protocol_value,
+                        total_value: total_value,
+                    }
+                );
+            }
+
+            // Burn the fund units and return all of the buckets to the caller
+            let coin_buckets: Vec<FungibleBucket> = coin_buckets.into_values().collect();
+            let fund_units_bucket = if fund_units_to_burn < fund_unit_amount {
+                fund_units_bucket.take(fund_units_to_burn).burn();
+
+                Some(fund_units_bucket)
+            } else {
+                fund_units_bucket.burn();
+
+                None
+            };
+            self.record_nav_checkpoint();
+
+            (coin_buckets, fund_units_bucket)
+        }
+
+        // Buy fund units by depositing XRD or any coin the oracle can price.
+        // The coins are invested in the DeFi protocol positions with the same logic used for the
+        // unstaked XRD and fund units are minted at the current gross value, minus the entry fee.
+        // The operation fails if less than min_fund_units would be minted.
+        pub fn subscribe(
+            &mut self,
+            coin_bucket: FungibleBucket,    // Coins to invest
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+            min_fund_units: Decimal,        // Minimum amount of fund units to receive
+        ) -> FungibleBucket                 // Fund units
+        {
+            assert!(
+                !self.deposits_paused,
+                "Deposits paused",
+            );
+            self.accrue_management_fee();
+
+            let coin = coin_bucket.resource_address();
+            let coin_amount = coin_bucket.amount();
+            assert!(
+                coin != self.fund_unit_resource_manager.address(),
+                "Wrong coin",
+            );
+            assert!(
+                coin_amount > Decimal::ZERO,
+                "Empty bucket",
+            );
+
+            // Compute the USD value of the deposited coins
+            let value = coin_amount * self.oracle_component.unwrap().get_price(
+                coin,
+                morpher_data.clone(),
+            );
+
+            // Get the current value of a fund unit
+            self.refresh_stale_defi_protocols_value(morpher_data.clone());
+            self.crystallize_performance_fee();
+            let fund_unit_gross_value = self.fund_unit_mint_value();
+
+            // Invest the coins in the DeFi protocol positions and get the value actually added to
+            // the fund, net of the losses in the swaps
+            let total_value_before = self.total_value;
+            let (_, defi_protocols_value) = self.deposit_split(
+                coin_bucket,
+                value,
+                morpher_data,
+            );
+            let deposited_value = (self.total_value - total_value_before).min(value);
+
+            // Compute the amount of fund units to mint, applying the entry fee, and make sure it
+            // is enough for the user
+            let fund_unit_amount =
+                (deposited_value * (100 - self.entry_fee)) / 100 / fund_unit_gross_value;
+            assert!(
+                fund_unit_amount >= min_fund_units,
+                "Fund units below minimum",
+            );
+
+            // Emit the SubscriptionEvent event
+            Runtime::emit_event(
+                SubscriptionEvent {
+                    coin: coin,
+                    coin_amount: coin_amount,
+                    value: value,
+                    fund_unit_amount: fund_unit_amount,
+                    defi_protocols_value: defi_protocols_value,
+                    total_value: self.total_value,
+                }
+            );
+
+            // Mint the fund units
+            let fund_units_bucket = self.fund_unit_resource_manager.mint(fund_unit_amount);
+            self.record_nav_checkpoint();
+
+            fund_units_bucket
+        }
+
+        // Stakers can deposit the Validator's LSUs and get fund units at their XRD redemption
+        // value.
+        // The LSUs are immediately unstaked; the claim NFT is stored with the ones created by
+        // start_unstake and the bot will invest the resulting XRD through finish_unstake without
+        // minting new fund units.
+        pub fn deposit_lsu(
+            &mut self,
+            lsu_bucket: FungibleBucket,     // The Validator's LSUs
+            morpher_data: HashMap<ResourceAddress, (String, String)>,
+            min_fund_units: Decimal,        // Minimum amount of fund units to receive
+        ) -> FungibleBucket                 // Fund units
+        {
+            assert!(
+                !self.deposits_paused,
+                "Deposits paused",
+            );
+            self.accrue_management_fee();
+
+            let lsu_amount = lsu_bucket.amount();
+            assert!(
+                lsu_amount > Decimal::ZERO,
+                "Empty bucket",
+            );
+
+            // Start LSU unstake and get the claim NFT; the Validator refuses any other coin
+            let claim_nft_bucket = self.validator.unstake(lsu_bucket);
+            let claim_nft_id = claim_nft_bucket.non_fungible_local_id();
+
+            // Get the amount of XRD that the claim NFT will be redeemed for and compute its USD
+            // value
+            let xrd_amount = claim_nft_bucket.non_fungible::<UnstakeData>().data().claim_amount;
+            let value = xrd_amount * self.oracle_component.unwrap().get_price(
+                XRD,
+                morpher_data.clone(),
+            );
+
+            // Compute the amount of fund units to mint at the current value and make sure it is
+            // enough for the user
+            self.refresh_stale_defi_protocols_value(morpher_data);
+            self.crystallize_performance_fee();
+            let fund_unit_gross_value = self.fund_unit_mint_value();
+            let fund_unit_amount = value / fund_unit_gross_value;
+            assert!(
+                fund_unit_amount >= min_fund_units,
+                "Fund units below minimum",
+            );
+
+            // Store the claim NFT and include its value in the fund total value
+            self.store_claim_nft(claim_nft_bucket);
+            self.prepaid_claims.insert(claim_nft_id.clone(), value);
+            self.total_value += value;
+
+            // Emit the LsuDepositEvent event
+            Runtime::emit_event(
+                LsuDepositEvent {
+                    lsu_amount: lsu_amount,
+                    xrd_amount: xrd_amount,
+                    claim_nft_id: claim_nft_id,
+                    fund_unit_amount: fund_unit_amount,
+                    total_value: self.total_value,
+                }
+            );
+
+            // Mint the fund units
+            let fund_units_bucket = self.fund_unit_resource_manager.mint(fund_unit_amount);
+            self.record_nav_checkpoint();
+
+            fund_units_bucket
+        }
+
+        // Set the dex wrapper component. The component mus implement the DexInterfaceScryptoStub
+        // interface
+        // An admin needs authorization from the other admins to call this method.
+        pub fn set_dex_component(
+            &mut self,
+            admin_proof: Proof,
+            dex: DexInterfaceScryptoStub,
+        ) {
+
+            // Check admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetDexComponent,
+                arguments_hash(&(dex,)),
+            ) {
+                return;
+            }
+
+            // Update the dex warapper component
+            self.dex = Some(dex);
+        }
+
+        // Set the withdrawal fee to pay when swapping fund units for coins.
+        // Admins must agree on the percentage when performing this operation.
+        pub fn set_withdrawal_fee(
+            &mut self,
+            admin_proof: Proof,
+            percentage: u8,
+        ) {
+
+            // Check admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetWithdrawalFee,
+                arguments_hash(&(percentage,)),
+            ) {
+                return;
+            }
+
+            // Make sure that percentage makes sense
+            assert!(
+                percentage < 100,
+                "Fee must be a number from 0 to 100 (excluded)"
+            );
+
+            // Update the fee
+            self.withdrawal_fee = percentage;
+        }
+
+        // Set the entry fee to pay when buying fund units through the subscribe method.
+        // Admins must agree on the percentage when performing this operation.
+        pub fn set_entry_fee(
+            &mut self,
+            admin_proof: Proof,
+            percentage: u8,
+        ) {
+
+            // Check admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetEntryFee,
+                arguments_hash(&(percentage,)),
+            ) {
+                return;
+            }
+
+            // Make sure that percentage makes sense
+            assert!(
+                percentage < 100,
+                "Fee must be a number from 0 to 100 (excluded)"
+            );
+
+            // Update the fee
+            self.entry_fee = percentage;
+        }
+
+        // Set the number of days after which the fund units that fund_units_distribution left in
+        // the AccountLocker and the ones not claimed from a Merkle distribution can be reclaimed
+        // by the bot.
+        // Admins must agree on the number of days when performing this operation.
+        pub fn set_unclaimed_fund_units_expiry(
+            &mut self,
+            admin_proof: Proof,
+            days: u16,
+        ) {
+
+            // Check admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetUnclaimedFundUnitsExpiry,
+                arguments_hash(&(days,)),
+            ) {
+                return;
+            }
+
+            // Update the expiry
+            self.unclaimed_fund_units_expiry = i64::from(days) * 86400;
+        }
+
+        // Set the number of days over which the fund units distributed by fund_units_distribution
+        // vest; zero means they are distributed liquid.
+        // Admins must agree on the number of days when performing this operation.
+        pub fn set_vesting_period(
+            &mut self,
+            admin_proof: Proof,
+            days: u16,
+        ) {
+
+            // Check admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetVestingPeriod,
+                arguments_hash(&(days,)),
+            ) {
+                return;
+            }
+
+            // Make sure no distribution is in progress so all of its stakers are treated the
+            // same way
+            assert!(
+                self.distribution_plan.is_none() ||
+                self.distribution_plan.as_ref().unwrap().paid_stakers == 0,
+                "Distribution in progress",
+            );
+
+            // Update the vesting period
+            self.vesting_period = i64::from(days) * 86400;
+        }
+
+        // Set the maximum age, in hours, of the cached value of a DeFi protocol position; older
+        // values are refreshed before minting or burning fund units.
+        // Admins must agree on the number of hours when performing this operation.
+        pub fn set_max_value_age(
+            &mut self,
+            admin_proof: Proof,
+            hours: u32,
+        ) {
+
+            // Check admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetMaxValueAge,
+                arguments_hash(&(hours,)),
+            ) {
+                return;
+            }
+
+            // Update the maximum age
+            self.max_value_age = i64::from(hours) * 3600;
+        }
+
+        // Set the oracle component wrapper to use. The component must implement the
+        // OracleInterfaceScryptoStub interface.
+        // An admin needs authorization from the other admins to call this method.
+        pub fn set_oracle_component(
+            &mut self,
+            admin_proof: Proof,
+            component: OracleInterfaceScryptoStub,
+        ) {
+
+            // Check other admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetOracleComponent,
+                arguments_hash(&(component,)),
+            ) {
+                return;
+            }
+
+            // Update the oracle component
+            self.oracle_component = Some(component);
+        }
+
+        // Set the allocation strategy component that decides where to deposit and withdraw from.
+        // The component must implement the AllocationStrategyInterfaceScryptoStub interface; None
+        // restores the built-in logic.
+        // An admin needs authorization from the other admins to call this method.
+        pub fn set_allocation_strategy(
+            &mut self,
+            admin_proof: Proof,
+            component: Option<AllocationStrategyInterfaceScryptoStub>,
+        ) {
+
+            // Check other admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetAllocationStrategy,
+                arguments_hash(&(component,)),
+            ) {
+                return;
+            }
+
+            // Update the allocation strategy component
+            self.allocation_strategy = component;
+        }
+
+        // Set the annual management fee percentage and the treasury account that receives it.
+        // Admins must agree both on the account and the percentage when performing this operation.
+        // The fee accrued so far is minted at the old percentage before the change.
+        pub fn set_management_fee(
+            &mut self,
+            admin_proof: Proof,
+            percentage: Decimal,
+            account: Global<Account>,
+        ) {
+            // Verify autorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetManagementFee,
+                arguments_hash(&(percentage, account)),
+            ) {
+                return;
+            }
+
+            // Make sure that percentage makes sense
+            assert!(
+                percentage >= Decimal::ZERO && percentage <= MAX_MANAGEMENT_FEE_PERCENTAGE,
+                "Fee must be a number from 0 to 10"
+            );
+
+            // Pay the fee accrued so far, then update percentage and account
+            self.accrue_management_fee();
+            self.management_fee_percentage = percentage;
+            self.management_fee_account = Some(account);
+        }
+
+        // Set the performance fee percentage, the crystallization period in days and the treasury
+        // account that receives the fee.
+        // Admins must agree on all of them when performing this operation.
+        // Changing these parameters never lowers the high-water mark.
+        pub fn set_performance_fee(
+            &mut self,
+            admin_proof: Proof,
+            percentage: u8,
+            crystallization_period_days: u16,
+            account: Global<Account>,
+        ) {
+            // Verify autorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetPerformanceFee,
+                arguments_hash(&(percentage, crystallization_period_days, account)),
+            ) {
+                return;
+            }
+
+            // Make sure that percentage makes sense
+            assert!(
+                percentage < 100,
+                "Fee must be a number from 0 to 100"
+            );
+
+            // Update percentage, period and account
+            self.performance_fee_percentage = percentage;
+            self.crystallization_period = i64::from(crystallization_period_days) * 86400;
+            self.performance_fee_account = Some(account);
+
+            // The first crystallization period starts now if the high-water mark was never set
+            if self.high_water_mark == Decimal::ZERO &&
+                self.fund_unit_resource_manager.total_supply().unwrap() > Decimal::ZERO {
+                self.accrue_management_fee();
+                let (_, high_water_mark) = self.fund_unit_value();
+                self.high_water_mark = high_water_mark;
+                self.last_crystallization = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+            }
+        }
+
+        // Set percentage and account address that manages the buyback fund.
+        // Admins must agree both on the account and the percentage when performing this operation
+        pub fn set_buyback_fund(
+            &mut self,
+            admin_proof: Proof,
+            percentage: u8,
+            account: Global<Account>,
+        ) {
+            // Verify autorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetBuybackFund,
+                arguments_hash(&(percentage, account)),
+            ) {
+                return;
+            }
+
+            // Make sure that percentage makes sense
+            assert!(
+                percentage < 100,
+                "Fee must be a number from 0 to 100"
+            );
+
+            // Update percentage and account
+            self.buyback_fund_percentage = percentage;
+            self.buyback_fund_account = account;
+        }
+
+        // Set the maximum percentage of the fund value that a single rebalance operation can move.
+        // Admins must agree on the percentage when performing this operation.
+        // Zero disables rebalancing.
+        pub fn set_rebalance_max_percentage(
+            &mut self,
+            admin_proof: Proof,
+            percentage: u8,
+        ) {
+
+            // Check admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetRebalanceMaxPercentage,
+                arguments_hash(&(percentage,)),
+            ) {
+                return;
+            }
+
+            // Make sure that percentage makes sense
+            assert!(
+                percentage <= 100,
+                "Percentage out of the 0-100 range"
+            );
+
+            // Update the percentage
+            self.rebalance_max_percentage = percentage;
+        }
+
+        // Set the minimum USD value to deposit in a DeFi protocol position when a deposit is split
+        // among multiple positions; this avoids spreading dust among the positions.
+        // Admins must agree on the value when performing this operation.
+        pub fn set_min_deposit_value(
+            &mut self,
+            admin_proof: Proof,
+            min_deposit_value: Decimal,
+        ) {
+
+            // Check admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetMinDepositValue,
+                arguments_hash(&(min_deposit_value,)),
+            ) {
+                return;
+            }
+
+            assert!(
+                min_deposit_value >= Decimal::ZERO,
+                "Negative value",
+            );
+
+            // Update the value
+            self.min_deposit_value = min_deposit_value;
+        }
+
+        // Set the minimum number of hours between two NAV checkpoints; zero means a checkpoint is
+        // written by every operation that changes the fund value.
+        // Admins must agree on the number of hours when performing this operation.
+        pub fn set_nav_checkpoint_interval(
+            &mut self,
+            admin_proof: Proof,
+            hours: u32,
+        ) {
+
+            // Check admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetNavCheckpointInterval,
+                arguments_hash(&(hours,)),
+            ) {
+                return;
+            }
+
+            // Update the interval
+            self.nav_checkpoint_interval = i64::from(hours) * 3600;
+        }
+
+        // Abort the distribution in progress, if any, when it can't be completed (e.g. the plan
+        // doesn't match the stakers): the fund units not distributed yet are burnt or, if
+        // roll_over is true, added to the next distribution.
+        // The stakers already paid keep their fund units.
+        // Admins must authorize this operation.
+        pub fn abort_distribution(
+            &mut self,
+            admin_proof: Proof,
+            roll_over: bool,
+        ) {
+
+            // Check admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::AbortDistribution,
+                arguments_hash(&(roll_over,)),
+            ) {
+                return;
+            }
+
+            assert!(
+                self.fund_units_vault.amount() > Decimal::ZERO || self.distribution_plan.is_some(),
+                "Nothing to distribute",
+            );
+
+            // Drop the plan and burn or roll over the fund units left in the vault
+            let distribution_id = self.distribution_plan.take().map(|plan| plan.id);
+            let fund_unit_amount = self.fund_units_vault.amount();
+            let fund_units_bucket = self.fund_units_vault.take_all();
+            if roll_over {
+                self.rolled_over_fund_units_vault.put(fund_units_bucket);
+            } else {
+                fund_units_bucket.burn();
+            }
+            self.fund_units_to_distribute = Decimal::ZERO;
+
+            // Emit the DistributionAbortedEvent event
+            Runtime::emit_event(
+                DistributionAbortedEvent {
+                    distribution_id: distribution_id,
+                    fund_unit_amount: fund_unit_amount,
+                    rolled_over: roll_over,
+                }
+            );
+        }
+
+        // A single admin can invoke this method to pause withdrawals, deposits and/or bot
+        // operations, i.e. while a DeFi protocol exploit or an oracle failure is investigated.
+        // Unpausing requires authorization from the other admins.
+        pub fn pause(
+            &mut self,
+            admin_proof: Proof,
+            withdrawals: bool,      // Pause withdrawals and the fund unit claims
+            deposits: bool,         // Pause subscribe, deposit_lsu and the admin deposits minting
+                                    // fund units
+            bot_operations: bool,   // Pause the bot operations moving funds, updating values or
+                                    // distributing fund units
+        ) {
+            // Verify the proof and get the id out of it
+            let admin_id = self.get_admin_id(admin_proof);
 
-                if percentage_diff > largest_percentage_diff {
-                    largest_percentage_diff = percentage_diff;
-                    largest_percentage_diff_name = Some(name.to_string());
+            assert!(
+                withdrawals || deposits || bot_operations,
+                "Nothing to pause",
+            );
+
+            self.withdrawals_paused = self.withdrawals_paused || withdrawals;
+            self.deposits_paused = self.deposits_paused || deposits;
+            self.bot_operations_paused = self.bot_operations_paused || bot_operations;
+
+            // Emit the PauseStateEvent event
+            Runtime::emit_event(
+                PauseStateEvent {
+                    withdrawals_paused: self.withdrawals_paused,
+                    deposits_paused: self.deposits_paused,
+                    bot_operations_paused: self.bot_operations_paused,
+                    admin_id: Some(admin_id),
+                    defi_protocol_name: None,
                 }
-            }
-            return (largest_percentage_diff_name.unwrap(), amount);
+            );
         }
 
-        // Swap fund units for any coin managed by a DeFi protocol or for a specific coin.
-        // This method withdraws from a single DeFi protocol position; if the full value can't be
-        // withdrawn from a single position, some fund units will be returned
-        pub fn withdraw(
+        // Resume withdrawals, deposits and/or bot operations.
+        // Admins must agree on what to unpause when performing this operation.
+        pub fn unpause(
             &mut self,
-            mut fund_units_bucket: FungibleBucket,  // Bucket of funds to swap
-            swap_to: Option<ResourceAddress>,       // If set, the returned coins will be swapped
-                                                    // to this coin
-            morpher_data: HashMap<ResourceAddress, (String, String)>,
-        ) -> (
-            FungibleBucket, // coin
-            Option<FungibleBucket>, // other coin
-            Option<FungibleBucket>, // fund units
+            admin_proof: Proof,
+            withdrawals: bool,      // Unpause withdrawals
+            deposits: bool,         // Unpause subscribe and deposit_lsu
+            bot_operations: bool,   // Unpause the bot operations
         ) {
+            let admin_id = self.get_admin_id(admin_proof);
+
             assert!(
-                fund_units_bucket.resource_address() == self.fund_unit_resource_manager.address(),
-                "Wrong coin",
+                !self.liquidating,
+                "Fund in liquidation",
             );
 
+            // Check admins' authorization
+            if !self.check_operation_authorization(
+                admin_id,
+                AuthorizedOperation::Unpause,
+                arguments_hash(&(withdrawals, deposits, bot_operations)),
+            ) {
+                return;
+            }
 
-            // Get the value of a fund unit
-            let (fund_unit_net_value, fund_unit_gross_value) = self.fund_unit_value();
+            self.withdrawals_paused = self.withdrawals_paused && !withdrawals;
+            self.deposits_paused = self.deposits_paused && !deposits;
+            self.bot_operations_paused = self.bot_operations_paused && !bot_operations;
 
-            // Find the DeFi protocol position to withdraw from
-            let fund_unit_amount = fund_units_bucket.amount();
-            let (defi_protocol_name, withdrawable_value) = self.find_where_to_withdraw_from(
-                fund_unit_amount * fund_unit_net_value
+            // Emit the PauseStateEvent event
+            Runtime::emit_event(
+                PauseStateEvent {
+                    withdrawals_paused: self.withdrawals_paused,
+                    deposits_paused: self.deposits_paused,
+                    bot_operations_paused: self.bot_operations_paused,
+                    admin_id: Some(admin_id),
+                    defi_protocol_name: None,
+                }
             );
-            let mut defi_protocol = self.defi_protocols.get_mut(&defi_protocol_name).unwrap();
+        }
 
-            // Get the price of the main coin managed from the choosen protocol
-            let coin_price = self.oracle_component.unwrap().get_price(
-                defi_protocol.coin,
-                morpher_data.clone()
+        // Set the percentage drop of the value of a DeFi protocol position in a single update that
+        // automatically pauses withdrawals, deposits and bot operations.
+        // Admins must agree on the percentage when performing this operation.
+        // Zero disables the circuit breaker.
+        pub fn set_circuit_breaker_percentage(
+            &mut self,
+            admin_proof: Proof,
+            percentage: u8,
+        ) {
+
+            // Check admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::SetCircuitBreakerPercentage,
+                arguments_hash(&(percentage,)),
+            ) {
+                return;
+            }
+
+            // Make sure that percentage makes sense
+            assert!(
+                percentage <= 100,
+                "Percentage out of the 0-100 range"
             );
 
-            // Compute the relative price of the two coins of the protocol
-            let (other_coin_to_coin_price_ratio, other_coin_price) = match defi_protocol.other_coin {
-                Some(other_coin) => {
-                    let other_coin_price = self.oracle_component.unwrap().get_price(
-                        other_coin,
-                        morpher_data
-                    );
+            // Update the percentage
+            self.circuit_breaker_percentage = percentage;
+        }
 
-                    (Some(other_coin_price / coin_price), Some(other_coin_price))
-                },
-                None => (None, None),
-            };
+        // This method returns the pause state of withdrawals, deposits and bot operations
+        pub fn pause_state(&self) -> (bool, bool, bool) {
+            (self.withdrawals_paused, self.deposits_paused, self.bot_operations_paused)
+        }
 
-            // Withdraw coins from the protocol position
-            let (mut coin_bucket, mut other_coin_bucket, coin_amount, other_coin_amount) = 
-                self.fund_manager_badge_vault.authorize_with_amount(
-                    1,
-                    || defi_protocol.wrapper.withdraw_coin(
-                        withdrawable_value / coin_price,
-                        other_coin_to_coin_price_ratio,
-                    )
-                );
+        // Start the liquidation of the fund: withdrawals, deposits and bot operations are paused
+        // forever and the admins can move the content of the DeFi protocol positions to the
+        // redemption vaults through liquidate_defi_protocol.
+        // The fund units held by the component and not assigned to anyone yet are burnt so that
+        // they don't dilute the redemptions; the ones already assigned to the stakers (Merkle
+        // distributions and vesting receipts) can still be claimed and redeemed.
+        // This operation can't be undone.
+        // Admins must authorize this operation.
+        pub fn start_liquidation(
+            &mut self,
+            admin_proof: Proof,
+        ) {
 
-            // Compute the total value of the returned buckets
-            let mut coin_bucket_value = coin_bucket.amount() * coin_price;
-            if other_coin_bucket.is_some() {
-                coin_bucket_value += other_coin_bucket.as_ref().unwrap().amount() * other_coin_price.unwrap();
+            // Check admins' authorization
+            if !self.check_operation_authorization(
+                self.get_admin_id(admin_proof),
+                AuthorizedOperation::StartLiquidation,
+                arguments_hash(&()),
+            ) {
+                return;
             }
 
-            // Update the protocol and total fund value
-            let mut new_protocol_value = coin_amount * coin_price;
-            if defi_protocol.other_coin.is_some() {
-                new_protocol_value += other_coin_price.unwrap() * other_coin_amount.unwrap();
-            }
-            self.total_value += new_protocol_value - defi_protocol.value;
-            defi_protocol.value = new_protocol_value;
+            assert!(
+                !self.liquidating,
+                "Fund already in liquidation",
+            );
 
-            // If swap_to was specified, swap both buckets for the specified coin (put everithing
-            // in coin_bucket, leave other_coin_bucket empty)
-            if swap_to.is_some() {
-                if swap_to.unwrap() != defi_protocol.coin {
-                    coin_bucket = self.fund_manager_badge_vault.authorize_with_amount(
-                        1,
-                        || FungibleBucket(
-                            self.dex.unwrap().swap(
-                                coin_bucket.into(),
-                                swap_to.unwrap(),
-                                false
-                            )
-                        )
-                    );
-                }
-                if other_coin_bucket.is_some() && swap_to.unwrap() != defi_protocol.other_coin.unwrap() {
-                    coin_bucket.put(
-                        self.fund_manager_badge_vault.authorize_with_amount(
-                            1,
-                            || FungibleBucket(
-                                self.dex.unwrap().swap(
-                                    other_coin_bucket.unwrap().into(),
-                                    swap_to.unwrap(),
-                                    false
-                                )
-                            )
-                        )
-                    );
+            // Mint the management fee accrued so far; no fee accrues during the liquidation
+            self.accrue_management_fee();
 
-                    other_coin_bucket = None;
-                }
-            }
+            self.liquidating = true;
+            self.withdrawals_paused = true;
+            self.deposits_paused = true;
+            self.bot_operations_paused = true;
 
-            // Compute the amount of fund units to burn
-            let mut fund_units_to_burn = coin_bucket_value / fund_unit_gross_value;
-            if fund_units_to_burn > fund_unit_amount {
-                assert!(
-                    fund_units_to_burn < fund_unit_amount * (1 + ACCEPTABLE_VALUE_DIFFERENCE),
-                    "Too much value withdrawn"
-                );
-                fund_units_to_burn = fund_unit_amount;
-            } else if fund_units_to_burn > fund_unit_amount * (1 - ACCEPTABLE_VALUE_DIFFERENCE) {
-                fund_units_to_burn = fund_unit_amount;
-            }
+            // Burn the fund units that have not been distributed yet and drop the distribution in
+            // progress, if any
+            let burnt_fund_units = self.fund_units_vault.amount() +
+                self.rolled_over_fund_units_vault.amount();
+            self.fund_units_vault.take_all().burn();
+            self.rolled_over_fund_units_vault.take_all().burn();
+            self.fund_units_to_distribute = Decimal::ZERO;
+            self.distribution_plan = None;
 
-            // Emit the WithdrawFromFundEvent event
+            // Emit the LiquidationStartedEvent event
             Runtime::emit_event(
-                WithdrawFromFundEvent {
-                    fund_unit_amount: fund_units_to_burn,
-                    defi_protocol_name: defi_protocol_name,
-                    protocol_value: new_protocol_value,
+                LiquidationStartedEvent {
                     total_value: self.total_value,
+                    burnt_fund_units: burnt_fund_units,
                 }
             );
+        }
 
-            // Burn the fund units and return all of the buckets to the caller
-            if fund_units_to_burn < fund_unit_amount {
-                fund_units_bucket.take(fund_units_to_burn).burn();
+        // Private method to put coins in the redemption vaults, eventually swapping them for XRD
+        fn put_in_redemption_vault(
+            &mut self,
+            bucket: FungibleBucket,
+            swap_to_xrd: bool,
+            coins: &mut IndexMap<ResourceAddress, Decimal>, // Amount of coins per resource
+        ) {
+            if bucket.amount() == Decimal::ZERO {
+                bucket.drop_empty();
+                return;
+            }
 
-                (coin_bucket, other_coin_bucket, Some(fund_units_bucket))
-            } else {
-                fund_units_bucket.burn();
+            let bucket = match swap_to_xrd && bucket.resource_address() != XRD {
+                true => self.swap_withdrawn_coins(bucket, None, XRD),
+                false => bucket,
+            };
 
-                (coin_bucket, other_coin_bucket, None)
+            let resource_address = bucket.resource_address();
+            *coins.entry(resource_address).or_insert(Decimal::ZERO) += bucket.amount();
+
+            if self.redemption_coins.contains(&resource_address) {
+                self.redemption_vaults.get_mut(&resource_address).unwrap().put(bucket);
+            } else {
+                self.redemption_coins.push(resource_address);
+                self.redemption_vaults.insert(
+                    resource_address,
+                    FungibleVault::with_bucket(bucket),
+                );
             }
         }
 
-        // Set the dex wrapper component. The component mus implement the DexInterfaceScryptoStub
-        // interface
-        // An admin needs authorization from the other admins to call this method.
-        pub fn set_dex_component(
+        // Exit a DeFi protocol position during the liquidation of the fund: all of the coins are
+        // withdrawn from the position through withdraw_all and put in the redemption vaults,
+        // eventually after swapping them for XRD; fungible protocol tokens still in the position
+        // are put in the redemption vaults too while non fungible ones are returned to the admin.
+        // The oracle is not used, so a broken oracle can't block the liquidation.
+        // If the coins are swapped for XRD, the operation fails if less than min_xrd_amount XRD are
+        // put in the redemption vaults.
+        // Admins must authorize this operation and agree on the position and on the swap.
+        pub fn liquidate_defi_protocol(
             &mut self,
             admin_proof: Proof,
-            dex: DexInterfaceScryptoStub,
-        ) {
+            name: String,       // Name of the DeFi protocol position to liquidate
+            swap_to_xrd: bool,  // Whether to swap the withdrawn coins for XRD
+            min_xrd_amount: Decimal, // Minimum amount of XRD to get if swap_to_xrd is true
+        ) -> Option<Bucket> {   // Non fungible protocol tokens
 
             // Check admins' authorization
-            self.check_operation_authorization(
+            if !self.check_operation_authorization(
                 self.get_admin_id(admin_proof),
-                AuthorizedOperation::SetDexComponent,
-                None,
-                None,
-                None,
+                AuthorizedOperation::LiquidateDefiProtocol,
+                arguments_hash(&(name.clone(), swap_to_xrd, min_xrd_amount)),
+            ) {
+                return None;
+            }
+
+            assert!(
+                self.liquidating,
+                "Fund not in liquidation",
             );
 
-            // Update the dex warapper component
-            self.dex = Some(dex);
+            // Remove the protocol position from the Vector and the KeyValueStore
+            self.defi_protocols_list.retain(|n| { *n != name });
+            let mut defi_protocol = self.defi_protocols.remove(&name)
+                .expect("Protocol not found");
+            self.total_value -= defi_protocol.value;
+
+            // Take everything out of the position
+            let (token_bucket, coin_bucket, other_coin_bucket) =
+                self.fund_manager_badge_vault.authorize_with_amount(
+                    1,
+                    || defi_protocol.wrapper.withdraw_all()
+                );
+
+            // Move the coins to the redemption vaults
+            let mut coins: IndexMap<ResourceAddress, Decimal> = IndexMap::new();
+            for bucket in [coin_bucket, other_coin_bucket].into_iter().flatten() {
+                self.put_in_redemption_vault(bucket, swap_to_xrd, &mut coins);
+            }
+            assert!(
+                !swap_to_xrd || *coins.get(&XRD).unwrap_or(&Decimal::ZERO) >= min_xrd_amount,
+                "Swap output below minimum",
+            );
+
+            // Fungible protocol tokens can be redeemed pro-rata too
+            let mut non_fungible_token_bucket: Option<Bucket> = None;
+            if token_bucket.resource_address().is_fungible() {
+                self.put_in_redemption_vault(FungibleBucket(token_bucket), false, &mut coins);
+            } else if token_bucket.is_empty() {
+                token_bucket.drop_empty();
+            } else {
+                non_fungible_token_bucket = Some(token_bucket);
+            }
+
+            // Emit the LiquidationEvent event
+            Runtime::emit_event(
+                LiquidationEvent {
+                    defi_protocol_name: Some(name),
+                    coins: coins,
+                }
+            );
+            self.record_nav_checkpoint();
+
+            non_fungible_token_bucket
         }
 
-        // Set the withdrawal fee to pay when swapping fund units for coins.
-        // Admins must agree on the percentage when performing this operation.
-        pub fn set_withdrawal_fee(
+        // During the liquidation of the fund anyone can invoke this method to claim the XRD of an
+        // unstaked claim NFT and put them in the redemption vaults
+        pub fn liquidate_claim_nft(
             &mut self,
-            admin_proof: Proof,
-            percentage: u8,
+            claim_nft_id: String, // String representation of the claim NFT id to unstake
         ) {
-
-            // Check admins' authorization
-            self.check_operation_authorization(
-                self.get_admin_id(admin_proof),
-                AuthorizedOperation::SetWithdrawalFee,
-                None,
-                Some(percentage),
-                None,
+            assert!(
+                self.liquidating,
+                "Fund not in liquidation",
             );
 
-            // Make sure that percentage makes sense
-            assert!(
-                percentage < 100,
-                "Fee must be a number from 0 to 100 (excluded)"
+            let claim_nft_id = NonFungibleLocalId::String(
+                StringNonFungibleLocalId::try_from(claim_nft_id).unwrap()
             );
 
-            // Update the fee
-            self.withdrawal_fee = percentage;
+            // The value of a prepaid claim NFT is no longer part of the fund value
+            let prepaid_value = self.prepaid_claims.remove(&claim_nft_id);
+            if prepaid_value.is_some() {
+                self.total_value -= prepaid_value.unwrap();
+            }
+
+            // Get the XRD out of the claim NFT
+            let mut claim_nft_ids: IndexSet<NonFungibleLocalId> = IndexSet::new();
+            claim_nft_ids.insert(claim_nft_id);
+            let xrd_bucket = FungibleBucket(self.claim_xrd(&claim_nft_ids));
+
+            let mut coins: IndexMap<ResourceAddress, Decimal> = IndexMap::new();
+            self.put_in_redemption_vault(xrd_bucket, false, &mut coins);
+
+            // Emit the LiquidationEvent event
+            Runtime::emit_event(
+                LiquidationEvent {
+                    defi_protocol_name: None,
+                    coins: coins,
+                }
+            );
+            self.record_nav_checkpoint();
         }
 
-        // Set the oracle component wrapper to use. The component must implement the
-        // OracleInterfaceScryptoStub interface.
-        // An admin needs authorization from the other admins to call this method.
-        pub fn set_oracle_component(
+        // Once the liquidation is completed, a fund unit holder can invoke this method to burn his
+        // fund units and receive his pro-rata share of the redemption vaults.
+        // There's no fee and no price is involved.
+        pub fn redeem(
             &mut self,
-            admin_proof: Proof,
-            component: OracleInterfaceScryptoStub,
-        ) {
+            fund_units_bucket: FungibleBucket,
+        ) -> Vec<FungibleBucket> {
+            assert!(
+                fund_units_bucket.resource_address() == self.fund_unit_resource_manager.address(),
+                "Wrong coin",
+            );
+            assert!(
+                self.liquidating &&
+                self.defi_protocols_list.len() == 0 &&
+                self.claim_nft_vault.amount() == Decimal::ZERO,
+                "Liquidation not completed",
+            );
 
-            // Check other admins' authorization
-            self.check_operation_authorization(
-                self.get_admin_id(admin_proof),
-                AuthorizedOperation::SetOracleComponent,
-                None,
-                None,
-                None,
+            // Compute the share of the redemption vaults
+            let fund_unit_amount = fund_units_bucket.amount();
+            let share = fund_unit_amount / self.fund_unit_resource_manager.total_supply().unwrap();
+            fund_units_bucket.burn();
+
+            // Take the share of each redemption vault
+            let mut coins: IndexMap<ResourceAddress, Decimal> = IndexMap::new();
+            let mut buckets: Vec<FungibleBucket> = vec![];
+            for resource_address in self.redemption_coins.iter() {
+                let mut vault = self.redemption_vaults.get_mut(resource_address).unwrap();
+
+                let amount = vault.amount() * share;
+                let bucket = vault.take_advanced(
+                    amount,
+                    WithdrawStrategy::Rounded(RoundingMode::ToZero),
+                );
+
+                coins.insert(*resource_address, bucket.amount());
+                buckets.push(bucket);
+            }
+
+            // Emit the RedemptionEvent event
+            Runtime::emit_event(
+                RedemptionEvent {
+                    fund_unit_amount: fund_unit_amount,
+                    coins: coins,
+                }
             );
+            self.record_nav_checkpoint();
 
-            // Update the oracle component
-            self.oracle_component = Some(component);
+            buckets
         }
 
-        // Set percentage and account address that manages the buyback fund.
-        // Admins must agree both on the account and the percentage when performing this operation
-        pub fn set_buyback_fund(
+        // This method returns the amount of each coin in the redemption vaults
+        pub fn redemption_amounts(&self) -> IndexMap<ResourceAddress, Decimal> {
+            let mut amounts: IndexMap<ResourceAddress, Decimal> = IndexMap::new();
+
+            for resource_address in self.redemption_coins.iter() {
+                amounts.insert(
+                    *resource_address,
+                    self.redemption_vaults.get(resource_address).unwrap().amount(),
+                );
+            }
+
+            amounts
+        }
+
+        // Set the hard caps on the USD value and on the percentage of the fund invested in a DeFi
+        // protocol position; None removes a cap.
+        // Deposits and rebalances skip the positions at their caps; admin deposits exceeding them
+        // fail.
+        // Admins must agree on the position and on the caps when performing this operation.
+        pub fn set_defi_protocol_caps(
             &mut self,
             admin_proof: Proof,
-            percentage: u8,
-            account: Global<Account>,
+            name: String,
+            max_value: Option<Decimal>,
+            max_percentage: Option<u8>,
         ) {
-            // Verify autorization
-            self.check_operation_authorization(
+
+            // Check admins' authorization
+            if !self.check_operation_authorization(
                 self.get_admin_id(admin_proof),
-                AuthorizedOperation::SetBuybackFund,
-                None,
-                Some(percentage),
-                Some(account),
-            );
+                AuthorizedOperation::SetDefiProtocolCaps,
+                arguments_hash(&(name.clone(), max_value, max_percentage)),
+            ) {
+                return;
+            }
 
-            // Make sure that percentage makes sense
+            // Make sure that the caps make sense
             assert!(
-                percentage < 100,
-                "Fee must be a number from 0 to 100"
+                max_value.map_or(true, |value| value >= Decimal::ZERO),
+                "Negative value",
+            );
+            assert!(
+                max_percentage.map_or(true, |percentage| percentage <= 100),
+                "Percentage out of the 0-100 range"
             );
 
-            // Update percentage and account
-            self.buyback_fund_percentage = percentage;
-            self.buyback_fund_account = account;
+            // Update the caps
+            let mut defi_protocol = self.defi_protocols.get_mut(&name)
+                .expect("Protocol not found");
+            defi_protocol.max_value = max_value;
+            defi_protocol.max_percentage = max_percentage;
         }
 
         // Register/ungegister the Validator
@@ -0,0 +1,2 @@
+mod mock_oracle;
+mod mock_defi_protocol;
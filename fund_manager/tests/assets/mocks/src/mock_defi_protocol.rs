@@ -0,0 +1,96 @@
+use scrypto::prelude::*;
+
+#[derive(ScryptoSbor, NonFungibleData)]
+struct AccountBadge {}
+
+// DeFi protocol wrapper used by the FundManager tests: it just keeps the deposited coins in a
+// vault and never issues protocol tokens
+#[blueprint]
+mod mock_defi_protocol {
+
+    struct MockDefiProtocol {
+        coin_vault: FungibleVault,
+        protocol_token: ResourceAddress,
+        account_badge_vault: NonFungibleVault,
+    }
+
+    impl MockDefiProtocol {
+
+        pub fn new(
+            coin: ResourceAddress,
+        ) -> (
+            Global<MockDefiProtocol>,
+            ResourceAddress, // Protocol token
+        ) {
+            let protocol_token = ResourceBuilder::new_fungible(OwnerRole::None)
+                .create_with_no_initial_supply()
+                .address();
+
+            let account_badge_bucket = ResourceBuilder::new_integer_non_fungible::<AccountBadge>(OwnerRole::None)
+                .mint_initial_supply([(IntegerNonFungibleLocalId::new(1), AccountBadge {})]);
+
+            let component = Self {
+                coin_vault: FungibleVault::new(coin),
+                protocol_token: protocol_token,
+                account_badge_vault: NonFungibleVault::with_bucket(account_badge_bucket),
+            }
+                .instantiate()
+                .prepare_to_globalize(OwnerRole::None)
+                .globalize();
+
+            (component, protocol_token)
+        }
+
+        pub fn deposit_all(
+            &mut self,
+            token: Bucket,
+            coin: Option<FungibleBucket>,
+            _other_coin: Option<FungibleBucket>,
+        ) -> (Decimal, Option<Decimal>) {
+            token.drop_empty();
+            if coin.is_some() {
+                self.coin_vault.put(coin.unwrap());
+            }
+
+            (self.coin_vault.amount(), None)
+        }
+
+        pub fn withdraw_all(&mut self) -> (Bucket, Option<FungibleBucket>, Option<FungibleBucket>) {
+            (
+                Bucket::new(self.protocol_token),
+                Some(self.coin_vault.take_all()),
+                None,
+            )
+        }
+
+        pub fn deposit_coin(
+            &mut self,
+            coin: FungibleBucket,
+            _other_coin: Option<FungibleBucket>,
+            _message: Option<String>,
+            _signature: Option<String>,
+        ) -> (Decimal, Option<Decimal>) {
+            self.coin_vault.put(coin);
+
+            (self.coin_vault.amount(), None)
+        }
+
+        pub fn withdraw_coin(
+            &mut self,
+            amount: Decimal,
+            _other_coin_to_coin_price_ratio: Option<Decimal>,
+        ) -> (FungibleBucket, Option<FungibleBucket>, Decimal, Option<Decimal>) {
+            let coin_bucket = self.coin_vault.take(amount);
+
+            (coin_bucket, None, self.coin_vault.amount(), None)
+        }
+
+        pub fn get_coin_amounts(&mut self) -> (Decimal, Option<Decimal>) {
+            (self.coin_vault.amount(), None)
+        }
+
+        pub fn withdraw_account_badge(&mut self) -> NonFungibleBucket {
+            self.account_badge_vault.take_all()
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use scrypto::prelude::*;
+
+// Oracle used by the FundManager tests: it returns the same price for every coin
+#[blueprint]
+mod mock_oracle {
+
+    struct MockOracle {
+        price: Decimal,
+    }
+
+    impl MockOracle {
+
+        pub fn new(
+            price: Decimal,
+        ) -> Global<MockOracle> {
+            Self {
+                price: price,
+            }
+                .instantiate()
+                .prepare_to_globalize(OwnerRole::None)
+                .globalize()
+        }
+
+        pub fn get_price(
+            &mut self,
+            _coin_address: ResourceAddress,
+            _morpher_data: HashMap<ResourceAddress, (String, String)>,
+        ) -> Decimal {
+            self.price
+        }
+
+        pub fn set_price(
+            &mut self,
+            price: Decimal,
+        ) {
+            self.price = price;
+        }
+    }
+}
@@ -0,0 +1,557 @@
+#![allow(dead_code)]
+
+use scrypto_test::prelude::*;
+
+// Invoke a method of the fund manager passing the proof of the badge of admin 1 as first argument
+macro_rules! admin_call {
+    ($test_fund:expr, $method:expr $(, $argument:expr)* $(,)?) => {{
+        let fund_manager = $test_fund.fund_manager;
+        let manifest = $test_fund.admin_manifest(1)
+            .call_method_with_name_lookup(fund_manager, $method, |lookup| (
+                lookup.proof("admin_proof"),
+                $($argument,)*
+            ))
+            .build();
+
+        $test_fund.execute(manifest)
+    }};
+}
+pub(crate) use admin_call;
+
+// Name of the DeFi protocol position created by TestFund::new
+pub const DEFI_PROTOCOL_NAME: &str = "mock";
+
+// Epochs to wait for the unlock of the Validator's owner LSUs
+const OWNER_STAKE_UNITS_UNLOCK_EPOCHS: u64 = 10_000;
+
+// A fund with 3 admin badges (no cosigner needed), 1000 fund units, the bot badge in the admin
+// account, a mock oracle pricing every coin 1 USD and a single mock DeFi protocol position
+// holding 1000 XRD; with_initial_supply creates the same fund with a different amount of fund
+// units and XRD.
+// The Validator owner badge is held by the fund manager with 5000 locked owner LSUs.
+pub struct TestFund {
+    pub ledger: DefaultLedgerSimulator,
+    pub public_key: Secp256k1PublicKey,
+    pub account: ComponentAddress,
+    pub validator: ComponentAddress,
+    pub lsu: ResourceAddress,
+    pub fund_manager: ComponentAddress,
+    pub admin_badge: ResourceAddress,
+    pub bot_badge: ResourceAddress,
+    pub fund_unit: ResourceAddress,
+    pub vesting_receipt: ResourceAddress,
+    pub account_lockers: Vec<ComponentAddress>,
+    pub oracle: ComponentAddress,
+    pub defi_protocol: ComponentAddress,
+    round: u64,
+    pub now: i64,
+}
+
+// An account that is not an admin of the fund
+pub struct TestAccount {
+    pub public_key: Secp256k1PublicKey,
+    pub address: ComponentAddress,
+}
+
+impl TestFund {
+
+    pub fn new() -> Self {
+        Self::with_initial_supply(dec!(1000))
+    }
+
+    pub fn with_initial_supply(initial_supply: Decimal) -> Self {
+        let mut ledger = LedgerSimulatorBuilder::new().build();
+        let (public_key, _, account) = ledger.new_allocated_account();
+
+        // Create a Validator accepting delegated stake and lock some owner LSUs
+        let validator = ledger.new_validator_with_pub_key(public_key, account);
+        let validator_info = ledger.get_validator_info(validator);
+        let lsu = validator_info.stake_unit_resource;
+        let claim_nft = validator_info.claim_nft;
+        let validator_badge_id = NonFungibleLocalId::bytes(validator.as_node_id().0).unwrap();
+        ledger.execute_manifest(
+            ManifestBuilder::new()
+                .lock_fee_from_faucet()
+                .get_free_xrd_from_faucet()
+                .create_proof_from_account_of_non_fungibles(
+                    account,
+                    VALIDATOR_OWNER_BADGE,
+                    [validator_badge_id.clone()],
+                )
+                .call_method(validator, "update_accept_delegated_stake", manifest_args!(true))
+                .take_from_worktop(XRD, dec!(5000), "xrd")
+                .call_method_with_name_lookup(validator, "stake", |lookup| (lookup.bucket("xrd"),))
+                .take_all_from_worktop(lsu, "lsu")
+                .call_method_with_name_lookup(validator, "lock_owner_stake_units", |lookup| (lookup.bucket("lsu"),))
+                .try_deposit_entire_worktop_or_abort(account, None)
+                .build(),
+            vec![NonFungibleGlobalId::from_public_key(&public_key)],
+        )
+            .expect_commit_success();
+
+        // Instantiate the FundManager and get its addresses
+        let package = ledger.compile_and_publish(this_package!());
+        let receipt = ledger.execute_manifest(
+            ManifestBuilder::new()
+                .lock_fee_from_faucet()
+                .call_function(
+                    package,
+                    "FundManager",
+                    "new",
+                    manifest_args!(validator, claim_nft, 0u8, 0u8, account),
+                )
+                .build(),
+            vec![],
+        );
+        let commit = receipt.expect_commit_success();
+        let fund_manager = *commit.new_component_addresses().iter()
+            .find(|address| address.as_node_id().entity_type() == Some(EntityType::GlobalGenericComponent))
+            .unwrap();
+        let account_lockers: Vec<ComponentAddress> = commit.new_component_addresses().iter()
+            .filter(|address| address.as_node_id().entity_type() == Some(EntityType::GlobalAccountLocker))
+            .cloned()
+            .collect();
+        let new_resources = commit.new_resource_addresses().clone();
+
+        let mut test_fund = Self {
+            ledger: ledger,
+            public_key: public_key,
+            account: account,
+            validator: validator,
+            lsu: lsu,
+            fund_manager: fund_manager,
+            admin_badge: XRD,
+            bot_badge: XRD,
+            fund_unit: XRD,
+            vesting_receipt: XRD,
+            account_lockers: account_lockers,
+            oracle: fund_manager,
+            defi_protocol: fund_manager,
+            round: 0,
+            now: 1_700_000_000,
+        };
+        test_fund.admin_badge = test_fund.resource_by_name(&new_resources, "Fund admin badge");
+        test_fund.bot_badge = test_fund.resource_by_name(&new_resources, "Fund bot badge");
+        test_fund.fund_unit = test_fund.resource_by_name(&new_resources, "Fund unit");
+        test_fund.vesting_receipt = test_fund.resource_by_name(&new_resources, "Fund unit vesting receipt");
+        test_fund.advance_time(0);
+
+        // Mint the admin badges and the initial fund units
+        test_fund.execute(
+            ManifestBuilder::new()
+                .lock_fee_from_faucet()
+                .call_method(fund_manager, "init", manifest_args!(3u8, 0u8, initial_supply))
+                .try_deposit_entire_worktop_or_abort(account, None)
+                .build()
+        )
+            .expect_commit_success();
+
+        // Create the mock oracle and DeFi protocol
+        let mocks_package = test_fund.ledger.compile_and_publish(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/assets/mocks")
+        );
+        test_fund.oracle = test_fund.execute(
+            ManifestBuilder::new()
+                .lock_fee_from_faucet()
+                .call_function(mocks_package, "MockOracle", "new", manifest_args!(dec!(1)))
+                .build()
+        )
+            .expect_commit_success()
+            .output::<ComponentAddress>(1);
+        let (defi_protocol, protocol_token) = test_fund.execute(
+            ManifestBuilder::new()
+                .lock_fee_from_faucet()
+                .call_function(mocks_package, "MockDefiProtocol", "new", manifest_args!(XRD))
+                .build()
+        )
+            .expect_commit_success()
+            .output::<(ComponentAddress, ResourceAddress)>(1);
+        test_fund.defi_protocol = defi_protocol;
+
+        // Configure the fund
+        let oracle = test_fund.oracle;
+        admin_call!(test_fund, "set_oracle_component", oracle)
+            .expect_commit_success();
+        admin_call!(
+            test_fund,
+            "add_defi_protocol",
+            DEFI_PROTOCOL_NAME.to_string(),
+            XRD,
+            protocol_token,
+            None::<ResourceAddress>,
+            100u8,
+            defi_protocol,
+            None::<ResourceAddress>,
+        )
+            .expect_commit_success();
+        admin_call!(test_fund, "mint_bot_badge", account)
+            .expect_commit_success();
+
+        // Give the fund the Validator owner badge and as many XRD as the initial fund units
+        let mut manifest_builder = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .get_free_xrd_from_faucet()
+            .create_proof_from_account_of_non_fungibles(account, test_fund.admin_badge, [NonFungibleLocalId::integer(1)])
+            .withdraw_non_fungibles_from_account(account, VALIDATOR_OWNER_BADGE, [validator_badge_id])
+            .take_all_from_worktop(VALIDATOR_OWNER_BADGE, "validator_badge")
+            .call_method_with_name_lookup(fund_manager, "deposit_validator_badge", |lookup| (
+                lookup.bucket("validator_badge"),
+            ));
+        if initial_supply > Decimal::ZERO {
+            manifest_builder = manifest_builder
+                .take_from_worktop(XRD, initial_supply, "xrd")
+                .call_method_with_name_lookup(fund_manager, "deposit_coin", |lookup| (
+                    DEFI_PROTOCOL_NAME.to_string(),
+                    lookup.bucket("xrd"),
+                    None::<ManifestBucket>,
+                    IndexMap::<ResourceAddress, (String, String)>::new(),
+                    false,
+                ));
+        }
+        test_fund.execute(
+            manifest_builder
+                .try_deposit_entire_worktop_or_abort(account, None)
+                .build()
+        )
+            .expect_commit_success();
+
+        test_fund
+    }
+
+    fn resource_by_name(
+        &mut self,
+        resources: &IndexSet<ResourceAddress>,
+        name: &str,
+    ) -> ResourceAddress {
+        *resources.iter()
+            .find(|resource| {
+                self.ledger.get_metadata(GlobalAddress::from(**resource), "name") ==
+                    Some(MetadataValue::String(name.to_string()))
+            })
+            .unwrap()
+    }
+
+    // Execute a manifest signed by the admin account
+    pub fn execute(
+        &mut self,
+        manifest: TransactionManifestV1,
+    ) -> TransactionReceipt {
+        self.ledger.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&self.public_key)],
+        )
+    }
+
+    // Manifest that puts the proof of an admin badge in the "admin_proof" named proof
+    pub fn admin_manifest(
+        &self,
+        admin_id: u64,
+    ) -> ManifestBuilder {
+        ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_non_fungibles(
+                self.account,
+                self.admin_badge,
+                [NonFungibleLocalId::integer(admin_id)],
+            )
+            .pop_from_auth_zone("admin_proof")
+    }
+
+    // Invoke a bot operation
+    pub fn bot_call(
+        &mut self,
+        method: &str,
+        arguments: ManifestArgs,
+    ) -> TransactionReceipt {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(self.account, self.bot_badge, dec!(1))
+            .call_method(self.fund_manager, method, arguments)
+            .try_deposit_entire_worktop_or_abort(self.account, None)
+            .build();
+
+        self.execute(manifest)
+    }
+
+    // Invoke a method that doesn't need any badge and return its output
+    pub fn query<T: ScryptoDecode>(
+        &mut self,
+        method: &str,
+        arguments: ManifestArgs,
+    ) -> T {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(self.fund_manager, method, arguments)
+            .build();
+
+        self.ledger.execute_manifest(manifest, vec![])
+            .expect_commit_success()
+            .output::<T>(1)
+    }
+
+    pub fn total_supply(&mut self) -> Decimal {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(self.fund_unit, "get_total_supply", manifest_args!())
+            .build();
+
+        self.ledger.execute_manifest(manifest, vec![])
+            .expect_commit_success()
+            .output::<Option<Decimal>>(1)
+            .unwrap()
+    }
+
+    // Gross USD value of a fund unit
+    pub fn fund_unit_gross_value(&mut self) -> Decimal {
+        self.query::<(Decimal, Decimal)>("fund_unit_value", manifest_args!()).1
+    }
+
+    pub fn balance(
+        &mut self,
+        account: ComponentAddress,
+        resource: ResourceAddress,
+    ) -> Decimal {
+        self.ledger.get_component_balance(account, resource)
+    }
+
+    pub fn locker_amount(
+        &mut self,
+        locker: ComponentAddress,
+        account: ComponentAddress,
+    ) -> Decimal {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(locker, "get_amount", manifest_args!(account, self.fund_unit))
+            .build();
+
+        self.ledger.execute_manifest(manifest, vec![])
+            .expect_commit_success()
+            .output::<Decimal>(1)
+    }
+
+    pub fn new_account(&mut self) -> TestAccount {
+        let (public_key, _, address) = self.ledger.new_allocated_account();
+
+        TestAccount {
+            public_key: public_key,
+            address: address,
+        }
+    }
+
+    // Make an account refuse every deposit not signed by its owner
+    pub fn reject_deposits(
+        &mut self,
+        account: &TestAccount,
+    ) {
+        self.ledger.execute_manifest(
+            ManifestBuilder::new()
+                .lock_fee_from_faucet()
+                .call_method(account.address, "set_default_deposit_rule", manifest_args!(DefaultDepositRule::Reject))
+                .build(),
+            vec![NonFungibleGlobalId::from_public_key(&account.public_key)],
+        )
+            .expect_commit_success();
+    }
+
+    // Move the ledger clock forward
+    pub fn advance_time(
+        &mut self,
+        seconds: i64,
+    ) {
+        self.round += 1;
+        self.now += seconds;
+        self.ledger.advance_to_round_at_timestamp(Round::of(self.round), self.now * 1000)
+            .expect_commit_success();
+    }
+
+    pub fn advance_epochs(
+        &mut self,
+        epochs: u64,
+    ) {
+        let epoch = self.ledger.get_current_epoch().number() + epochs;
+        self.ledger.set_current_epoch(Epoch::of(epoch));
+    }
+
+    // Claim NFTs held by the fund: number, id, claim epoch, XRD amount and whether they come from
+    // deposit_lsu
+    pub fn pending_claims(
+        &mut self,
+        start_number: Option<u64>,
+    ) -> Vec<(u64, NonFungibleLocalId, u64, Decimal, bool)> {
+        self.query("pending_claims", manifest_args!(start_number))
+    }
+
+    // Wait until every claim NFT held by the fund can be claimed
+    pub fn wait_for_claims(&mut self) {
+        let last_claim_epoch = self.pending_claims(None).iter()
+            .map(|claim| claim.2)
+            .max()
+            .unwrap();
+        let current_epoch = self.ledger.get_current_epoch().number();
+        if last_claim_epoch > current_epoch {
+            self.advance_epochs(last_claim_epoch - current_epoch);
+        }
+    }
+
+    // Unstake some of the Validator's owner LSUs and get a claim NFT in the fund
+    pub fn start_owner_unstake(
+        &mut self,
+        lsu_amount: Decimal,
+    ) {
+        self.bot_call("start_unlock_owner_stake_units", manifest_args!(lsu_amount))
+            .expect_commit_success();
+        self.advance_epochs(OWNER_STAKE_UNITS_UNLOCK_EPOCHS);
+        self.bot_call("start_unstake", manifest_args!())
+            .expect_commit_success();
+    }
+
+    // Unstake some of the Validator's owner LSUs and invest the XRD so that new fund units to
+    // distribute are minted; returns the amount of fund units to distribute
+    pub fn new_fund_units_to_distribute(
+        &mut self,
+        lsu_amount: Decimal,
+    ) -> Decimal {
+        self.start_owner_unstake(lsu_amount);
+        self.wait_for_claims();
+
+        let total_supply = self.total_supply();
+        self.bot_call(
+            "finish_matured_unstakes",
+            manifest_args!(IndexMap::<ResourceAddress, (String, String)>::new()),
+        )
+            .expect_commit_success();
+
+        // One more fund unit is minted to cover rounding
+        self.total_supply() - total_supply - dec!(1)
+    }
+
+    // Refresh the value of the DeFi protocol position with the bot; this also pays the fees
+    pub fn update_defi_protocols_value(&mut self) -> TransactionReceipt {
+        self.bot_call(
+            "update_defi_protocols_value",
+            manifest_args!(
+                IndexSet::from([DEFI_PROTOCOL_NAME.to_string()]),
+                IndexMap::<ResourceAddress, (String, String)>::new(),
+            ),
+        )
+    }
+
+    // Stake XRD from the admin account with the Validator and deposit the LSUs in the fund;
+    // returns the received fund units
+    pub fn deposit_lsu(
+        &mut self,
+        xrd_amount: Decimal,
+    ) -> Decimal {
+        let fund_units = self.balance(self.account, self.fund_unit);
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .get_free_xrd_from_faucet()
+            .take_from_worktop(XRD, xrd_amount, "xrd")
+            .call_method_with_name_lookup(self.validator, "stake", |lookup| (lookup.bucket("xrd"),))
+            .take_all_from_worktop(self.lsu, "lsu")
+            .call_method_with_name_lookup(self.fund_manager, "deposit_lsu", |lookup| (
+                lookup.bucket("lsu"),
+                IndexMap::<ResourceAddress, (String, String)>::new(),
+                Decimal::ZERO,
+            ))
+            .try_deposit_entire_worktop_or_abort(self.account, None)
+            .build();
+        self.execute(manifest)
+            .expect_commit_success();
+
+        self.balance(self.account, self.fund_unit) - fund_units
+    }
+
+    // Subscribe XRD from the admin account
+    pub fn subscribe(
+        &mut self,
+        xrd_amount: Decimal,
+    ) -> TransactionReceipt {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .get_free_xrd_from_faucet()
+            .take_from_worktop(XRD, xrd_amount, "xrd")
+            .call_method_with_name_lookup(self.fund_manager, "subscribe", |lookup| (
+                lookup.bucket("xrd"),
+                IndexMap::<ResourceAddress, (String, String)>::new(),
+                Decimal::ZERO,
+            ))
+            .try_deposit_entire_worktop_or_abort(self.account, None)
+            .build();
+
+        self.execute(manifest)
+    }
+
+    // Commit a distribution plan for the stakers in the given order
+    pub fn commit_distribution_plan(
+        &mut self,
+        stakers: &Vec<(ComponentAddress, Decimal)>,
+    ) -> TransactionReceipt {
+        let mut plan_hash = Hash([0u8; 32]);
+        for (account, share) in stakers.iter() {
+            plan_hash = distribution_plan_hash(plan_hash, *account, *share);
+        }
+        let number_of_stakers = stakers.len() as u64;
+
+        self.bot_call("commit_distribution_plan", manifest_args!(plan_hash, number_of_stakers))
+    }
+
+    pub fn fund_units_distribution(
+        &mut self,
+        stakers: &Vec<(ComponentAddress, Decimal)>,
+        more_stakers: bool,
+    ) -> TransactionReceipt {
+        let stakers: IndexMap<ComponentAddress, Decimal> = stakers.iter().cloned().collect();
+
+        self.bot_call("fund_units_distribution", manifest_args!(stakers, more_stakers))
+    }
+}
+
+// Same computation as distribution_plan_hash in the blueprint
+pub fn distribution_plan_hash(
+    previous_hash: Hash,
+    account: ComponentAddress,
+    share: Decimal,
+) -> Hash {
+    hash(scrypto_encode(&(previous_hash, account, share)).unwrap())
+}
+
+// Same computation as merkle_leaf in the blueprint
+pub fn merkle_leaf(
+    distribution_id: u64,
+    account: ComponentAddress,
+    share: Decimal,
+) -> Hash {
+    hash(scrypto_encode(&(distribution_id, account, share)).unwrap())
+}
+
+// Parent node of two Merkle tree nodes
+pub fn merkle_node(
+    a: Hash,
+    b: Hash,
+) -> Hash {
+    let (first, second) = match a.0 <= b.0 {
+        true => (a, b),
+        false => (b, a),
+    };
+    let mut data = first.0.to_vec();
+    data.extend_from_slice(&second.0);
+
+    hash(data)
+}
+
+pub fn arguments_hash<T: ScryptoEncode + ?Sized>(arguments: &T) -> Hash {
+    hash(scrypto_encode(arguments).unwrap())
+}
+
+pub fn assert_close(
+    a: Decimal,
+    b: Decimal,
+) {
+    assert!(
+        (a - b).checked_abs().unwrap() <= dec!("0.000001"),
+        "{} != {}",
+        a,
+        b,
+    );
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use common::*;
+use scrypto_test::prelude::*;
+
+// New fund units to distribute and a committed plan for three new accounts
+fn setup() -> (TestFund, Decimal, Vec<(ComponentAddress, Decimal)>) {
+    let mut test_fund = TestFund::new();
+    let fund_unit_amount = test_fund.new_fund_units_to_distribute(dec!(100));
+    let stakers = vec![
+        (test_fund.new_account().address, dec!("0.5")),
+        (test_fund.new_account().address, dec!("0.3")),
+        (test_fund.new_account().address, dec!("0.2")),
+    ];
+
+    test_fund.commit_distribution_plan(&stakers)
+        .expect_commit_success();
+
+    (test_fund, fund_unit_amount, stakers)
+}
+
+#[test]
+fn distribution_plan_is_paid_in_chunks() {
+    let (mut test_fund, fund_unit_amount, stakers) = setup();
+    let total_supply = test_fund.total_supply();
+
+    test_fund.fund_units_distribution(&stakers[..1].to_vec(), true)
+        .expect_commit_success();
+    let (_, _, _, _, cumulative_share, paid_stakers) = test_fund.query::<Option<(u64, Hash, u64, Hash, Decimal, u64)>>(
+        "distribution_plan",
+        manifest_args!(),
+    )
+        .unwrap();
+    assert_eq!(cumulative_share, dec!("0.5"));
+    assert_eq!(paid_stakers, 1);
+
+    test_fund.fund_units_distribution(&stakers[1..].to_vec(), false)
+        .expect_commit_success();
+
+    // Every staker got its share and only the extra fund unit is burnt
+    let fund_unit = test_fund.fund_unit;
+    for (account, share) in stakers.iter() {
+        assert_eq!(test_fund.balance(*account, fund_unit), fund_unit_amount * *share);
+    }
+    assert_close(test_fund.total_supply(), total_supply - dec!(1));
+    assert!(
+        test_fund.query::<Option<(u64, Hash, u64, Hash, Decimal, u64)>>("distribution_plan", manifest_args!())
+            .is_none()
+    );
+}
+
+#[test]
+fn distribution_not_matching_the_plan_can_be_aborted() {
+    let (mut test_fund, fund_unit_amount, stakers) = setup();
+
+    test_fund.fund_units_distribution(&stakers[..1].to_vec(), true)
+        .expect_commit_success();
+
+    // The last chunk doesn't match the committed plan
+    let wrong_stakers = vec![
+        stakers[1],
+        (test_fund.new_account().address, dec!("0.2")),
+    ];
+    test_fund.fund_units_distribution(&wrong_stakers, false)
+        .expect_commit_failure();
+
+    // The plan can't be replaced once the first chunk is paid
+    test_fund.commit_distribution_plan(&wrong_stakers)
+        .expect_commit_failure();
+
+    // Abort keeping the undistributed fund units for the next distribution
+    let total_supply = test_fund.total_supply();
+    admin_call!(test_fund, "abort_distribution", true)
+        .expect_commit_success();
+    assert!(
+        test_fund.query::<Option<(u64, Hash, u64, Hash, Decimal, u64)>>("distribution_plan", manifest_args!())
+            .is_none()
+    );
+    assert_eq!(test_fund.total_supply(), total_supply);
+
+    // The staker already paid keeps its fund units
+    let fund_unit = test_fund.fund_unit;
+    assert_eq!(test_fund.balance(stakers[0].0, fund_unit), fund_unit_amount * stakers[0].1);
+
+    // Nothing left to abort
+    admin_call!(test_fund, "abort_distribution", true)
+        .expect_commit_failure();
+}
+
+#[test]
+fn aborted_distribution_fund_units_can_be_burnt() {
+    let (mut test_fund, fund_unit_amount, _) = setup();
+
+    let total_supply = test_fund.total_supply();
+    admin_call!(test_fund, "abort_distribution", false)
+        .expect_commit_success();
+
+    // The fund units to distribute and the extra one are burnt
+    assert_eq!(test_fund.total_supply(), total_supply - fund_unit_amount - dec!(1));
+}
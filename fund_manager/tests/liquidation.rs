@@ -0,0 +1,98 @@
+mod common;
+
+use common::*;
+use scrypto_test::prelude::*;
+
+fn liquidate_defi_protocol(
+    test_fund: &mut TestFund,
+    min_xrd_amount: Decimal,
+) -> TransactionReceipt {
+    admin_call!(
+        test_fund,
+        "liquidate_defi_protocol",
+        DEFI_PROTOCOL_NAME.to_string(),
+        true,
+        min_xrd_amount,
+    )
+}
+
+#[test]
+fn liquidation_burns_the_undistributed_fund_units() {
+    let mut test_fund = TestFund::new();
+
+    let fund_units_to_distribute = test_fund.new_fund_units_to_distribute(dec!(100));
+    assert!(fund_units_to_distribute > Decimal::ZERO);
+    assert_eq!(test_fund.total_supply(), dec!(1000) + fund_units_to_distribute + dec!(1));
+
+    admin_call!(test_fund, "start_liquidation")
+        .expect_commit_success();
+
+    // Only the fund units owned by someone are left
+    assert_eq!(test_fund.total_supply(), dec!(1000));
+
+    // The undistributed fund units can no longer be distributed
+    let stakers = vec![(test_fund.account, dec!(1))];
+    test_fund.commit_distribution_plan(&stakers)
+        .expect_commit_failure();
+}
+
+#[test]
+fn liquidation_redeems_pro_rata() {
+    let mut test_fund = TestFund::new();
+    test_fund.new_fund_units_to_distribute(dec!(100));
+
+    admin_call!(test_fund, "start_liquidation")
+        .expect_commit_success();
+
+    // Deposits minting fund units are refused
+    let fund_manager = test_fund.fund_manager;
+    let account = test_fund.account;
+    let admin_badge = test_fund.admin_badge;
+    test_fund.execute(
+        ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .get_free_xrd_from_faucet()
+            .create_proof_from_account_of_non_fungibles(account, admin_badge, [NonFungibleLocalId::integer(1)])
+            .take_from_worktop(XRD, dec!(100), "xrd")
+            .call_method_with_name_lookup(fund_manager, "deposit_coin", |lookup| (
+                DEFI_PROTOCOL_NAME.to_string(),
+                lookup.bucket("xrd"),
+                None::<ManifestBucket>,
+                IndexMap::<ResourceAddress, (String, String)>::new(),
+                true,
+            ))
+            .try_deposit_entire_worktop_or_abort(account, None)
+            .build()
+    )
+        .expect_commit_failure();
+
+    // The position holds the initial 1000 XRD plus the unstaked ones: the minimum XRD amount is
+    // enforced
+    liquidate_defi_protocol(&mut test_fund, dec!(1200))
+        .expect_commit_failure();
+    liquidate_defi_protocol(&mut test_fund, dec!(1000))
+        .expect_commit_success();
+    let redemption_xrd = *test_fund.query::<IndexMap<ResourceAddress, Decimal>>("redemption_amounts", manifest_args!())
+        .get(&XRD)
+        .unwrap();
+    assert!(redemption_xrd > dec!(1000));
+
+    // Half of the fund units get half of the XRD
+    let xrd_balance = test_fund.balance(account, XRD);
+    let fund_unit = test_fund.fund_unit;
+    test_fund.execute(
+        ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(account, fund_unit, dec!(500))
+            .take_all_from_worktop(fund_unit, "fund_units")
+            .call_method_with_name_lookup(fund_manager, "redeem", |lookup| (
+                lookup.bucket("fund_units"),
+            ))
+            .try_deposit_entire_worktop_or_abort(account, None)
+            .build()
+    )
+        .expect_commit_success();
+
+    assert_close(test_fund.balance(account, XRD) - xrd_balance, redemption_xrd / 2);
+    assert_eq!(test_fund.total_supply(), dec!(500));
+}
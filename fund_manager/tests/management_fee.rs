@@ -0,0 +1,53 @@
+mod common;
+
+use common::*;
+use scrypto_test::prelude::*;
+
+// 365 days, as in the blueprint
+const SECONDS_PER_YEAR: i64 = 31536000;
+
+// A fund with a 2% management fee paid to a new treasury account
+fn setup() -> (TestFund, ComponentAddress) {
+    let mut test_fund = TestFund::new();
+    let treasury = test_fund.new_account().address;
+
+    admin_call!(test_fund, "set_management_fee", dec!(2), treasury)
+        .expect_commit_success();
+
+    (test_fund, treasury)
+}
+
+#[test]
+fn management_fee_is_minted_pro_rata_temporis() {
+    let (mut test_fund, treasury) = setup();
+    let fund_unit = test_fund.fund_unit;
+
+    // Half a year at 2% is a fee fraction of 1%
+    test_fund.advance_time(SECONDS_PER_YEAR / 2);
+    let expected_amount = dec!(1000) * dec!("0.01") / (1 - dec!("0.01"));
+    assert_close(test_fund.query::<Decimal>("management_fee_preview", manifest_args!()), expected_amount);
+
+    test_fund.update_defi_protocols_value()
+        .expect_commit_success();
+
+    // The minted fund units are worth 1% of the fund
+    assert_close(test_fund.balance(treasury, fund_unit), expected_amount);
+    assert_close(test_fund.total_supply(), dec!(1000) + expected_amount);
+    assert_close(test_fund.fund_unit_gross_value(), dec!("0.99"));
+    assert_eq!(test_fund.query::<Decimal>("management_fee_preview", manifest_args!()), Decimal::ZERO);
+}
+
+#[test]
+fn management_fee_accrues_at_most_one_year() {
+    let (mut test_fund, treasury) = setup();
+    let fund_unit = test_fund.fund_unit;
+
+    test_fund.advance_time(2 * SECONDS_PER_YEAR);
+    test_fund.update_defi_protocols_value()
+        .expect_commit_success();
+
+    // Only one year of fee is taken
+    let expected_amount = dec!(1000) * dec!("0.02") / (1 - dec!("0.02"));
+    assert_close(test_fund.balance(treasury, fund_unit), expected_amount);
+    assert_close(test_fund.fund_unit_gross_value(), dec!("0.98"));
+}
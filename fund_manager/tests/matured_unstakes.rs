@@ -0,0 +1,79 @@
+mod common;
+
+use common::*;
+use scrypto_test::prelude::*;
+
+fn finish_matured_unstakes(test_fund: &mut TestFund) -> TransactionReceipt {
+    test_fund.bot_call(
+        "finish_matured_unstakes",
+        manifest_args!(IndexMap::<ResourceAddress, (String, String)>::new()),
+    )
+}
+
+#[test]
+fn prepaid_claims_do_not_dilute_the_new_fund_units() {
+    let mut test_fund = TestFund::new();
+
+    // A prepaid claim NFT worth 1000 XRD and a new one worth 100 XRD mature together
+    assert_eq!(test_fund.deposit_lsu(dec!(1000)), dec!(1000));
+    test_fund.start_owner_unstake(dec!(100));
+    test_fund.wait_for_claims();
+    let pending_claims = test_fund.pending_claims(None);
+    assert_eq!(pending_claims.len(), 2);
+    assert!(pending_claims[0].4);
+    assert!(!pending_claims[1].4);
+
+    let total_supply = test_fund.total_supply();
+    assert_eq!(test_fund.fund_unit_gross_value(), dec!(1));
+    finish_matured_unstakes(&mut test_fund)
+        .expect_commit_success();
+
+    // The new fund units are minted at the value before the prepaid value was removed, plus the
+    // extra fund unit
+    assert_eq!(test_fund.total_supply(), total_supply + dec!(101));
+    assert!(test_fund.pending_claims(None).is_empty());
+}
+
+#[test]
+fn only_matured_claims_are_finished() {
+    let mut test_fund = TestFund::new();
+
+    // Two prepaid claim NFTs with different claim epochs
+    test_fund.deposit_lsu(dec!(500));
+    test_fund.advance_epochs(1);
+    test_fund.deposit_lsu(dec!(300));
+
+    let pending_claims = test_fund.pending_claims(None);
+    assert_eq!(pending_claims.len(), 2);
+    assert_eq!((pending_claims[0].0, pending_claims[1].0), (0, 1));
+    assert!(pending_claims[0].2 < pending_claims[1].2);
+    let paginated_claims = test_fund.pending_claims(Some(1));
+    assert_eq!(paginated_claims.len(), 1);
+    assert_eq!(paginated_claims[0].1, pending_claims[1].1);
+
+    // Only the first claim NFT matures
+    let current_epoch = test_fund.ledger.get_current_epoch().number();
+    if pending_claims[0].2 > current_epoch {
+        test_fund.advance_epochs(pending_claims[0].2 - current_epoch);
+    }
+    let total_supply = test_fund.total_supply();
+    finish_matured_unstakes(&mut test_fund)
+        .expect_commit_success();
+
+    // No fund units are minted for prepaid claim NFTs and the other one keeps its number
+    assert_eq!(test_fund.total_supply(), total_supply);
+    let remaining_claims = test_fund.pending_claims(None);
+    assert_eq!(remaining_claims.len(), 1);
+    assert_eq!(remaining_claims[0].0, 1);
+    assert_eq!(remaining_claims[0].1, pending_claims[1].1);
+
+    test_fund.wait_for_claims();
+    finish_matured_unstakes(&mut test_fund)
+        .expect_commit_success();
+    assert!(test_fund.pending_claims(None).is_empty());
+    assert_close(test_fund.fund_unit_gross_value(), dec!(1));
+
+    // Nothing left to finish
+    finish_matured_unstakes(&mut test_fund)
+        .expect_commit_failure();
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use common::*;
+use scrypto_test::prelude::*;
+
+// A Merkle distribution of newly minted fund units between two new accounts owning 25% and 75%
+// of the stake
+struct TestDistribution {
+    test_fund: TestFund,
+    distribution_id: u64,
+    fund_unit_amount: Decimal,
+    stakers: Vec<(ComponentAddress, Decimal)>,
+}
+
+impl TestDistribution {
+
+    fn new() -> Self {
+        let mut test_fund = TestFund::new();
+        let fund_unit_amount = test_fund.new_fund_units_to_distribute(dec!(100));
+        let stakers = vec![
+            (test_fund.new_account().address, dec!("0.25")),
+            (test_fund.new_account().address, dec!("0.75")),
+        ];
+
+        let total_supply = test_fund.total_supply();
+
+        // First Merkle distribution of the fund
+        let distribution_id = 1u64;
+        let merkle_root = merkle_node(
+            merkle_leaf(distribution_id, stakers[0].0, stakers[0].1),
+            merkle_leaf(distribution_id, stakers[1].0, stakers[1].1),
+        );
+        let committed_distribution_id = test_fund.bot_call("commit_distribution_merkle_root", manifest_args!(merkle_root))
+            .expect_commit_success()
+            .output::<u64>(2);
+        assert_eq!(committed_distribution_id, distribution_id);
+
+        // Exactly the fund units to distribute are moved to the distribution
+        assert_eq!(test_fund.total_supply(), total_supply - dec!(1));
+        let (_, distributed_amount, unclaimed_amount) = test_fund.query::<(Hash, Decimal, Decimal)>(
+            "merkle_distribution",
+            manifest_args!(distribution_id),
+        );
+        assert_eq!(distributed_amount, fund_unit_amount);
+        assert_eq!(unclaimed_amount, fund_unit_amount);
+
+        Self {
+            test_fund: test_fund,
+            distribution_id: distribution_id,
+            fund_unit_amount: fund_unit_amount,
+            stakers: stakers,
+        }
+    }
+
+    // Claim the share of a staker using the leaf of the other one as proof
+    fn claim(
+        &mut self,
+        staker: usize,
+    ) -> TransactionReceipt {
+        let (account, share) = self.stakers[staker];
+        let (other_account, other_share) = self.stakers[1 - staker];
+        let merkle_proof = vec![merkle_leaf(self.distribution_id, other_account, other_share)];
+
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(
+                self.test_fund.fund_manager,
+                "claim_fund_units",
+                manifest_args!(self.distribution_id, account, share, merkle_proof),
+            )
+            .build();
+
+        self.test_fund.execute(manifest)
+    }
+
+    fn sweep(
+        &mut self,
+        roll_over: bool,
+    ) -> TransactionReceipt {
+        self.test_fund.bot_call("sweep_merkle_distribution", manifest_args!(self.distribution_id, roll_over))
+    }
+}
+
+#[test]
+fn merkle_distribution_can_be_claimed_once() {
+    let mut test_distribution = TestDistribution::new();
+    let account = test_distribution.stakers[0].0;
+    let fund_unit = test_distribution.test_fund.fund_unit;
+
+    test_distribution.claim(0)
+        .expect_commit_success();
+    assert_eq!(
+        test_distribution.test_fund.balance(account, fund_unit),
+        test_distribution.fund_unit_amount * dec!("0.25"),
+    );
+
+    test_distribution.claim(0)
+        .expect_commit_failure();
+    assert_eq!(
+        test_distribution.test_fund.balance(account, fund_unit),
+        test_distribution.fund_unit_amount * dec!("0.25"),
+    );
+}
+
+#[test]
+fn expired_merkle_distribution_is_swept() {
+    let mut test_distribution = TestDistribution::new();
+    test_distribution.claim(0)
+        .expect_commit_success();
+
+    // Not expired yet
+    test_distribution.sweep(false)
+        .expect_commit_failure();
+
+    admin_call!(test_distribution.test_fund, "set_unclaimed_fund_units_expiry", 1u16)
+        .expect_commit_success();
+    test_distribution.test_fund.advance_time(86401);
+
+    let total_supply = test_distribution.test_fund.total_supply();
+    test_distribution.sweep(false)
+        .expect_commit_success();
+
+    // The unclaimed fund units are burnt and can no longer be claimed
+    assert_eq!(
+        test_distribution.test_fund.total_supply(),
+        total_supply - test_distribution.fund_unit_amount * dec!("0.75"),
+    );
+    test_distribution.claim(1)
+        .expect_commit_failure();
+    test_distribution.sweep(false)
+        .expect_commit_failure();
+}
+
+#[test]
+fn swept_merkle_distribution_can_be_rolled_over() {
+    let mut test_distribution = TestDistribution::new();
+
+    admin_call!(test_distribution.test_fund, "set_unclaimed_fund_units_expiry", 1u16)
+        .expect_commit_success();
+    test_distribution.test_fund.advance_time(86401);
+
+    let total_supply = test_distribution.test_fund.total_supply();
+    test_distribution.sweep(true)
+        .expect_commit_success();
+
+    // The fund units are kept for the next distribution
+    assert_eq!(test_distribution.test_fund.total_supply(), total_supply);
+    let (_, _, unclaimed_amount) = test_distribution.test_fund.query::<(Hash, Decimal, Decimal)>(
+        "merkle_distribution",
+        manifest_args!(test_distribution.distribution_id),
+    );
+    assert_eq!(unclaimed_amount, Decimal::ZERO);
+    test_distribution.claim(0)
+        .expect_commit_failure();
+}
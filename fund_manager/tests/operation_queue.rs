@@ -0,0 +1,144 @@
+mod common;
+
+use common::*;
+use scrypto_test::prelude::*;
+
+// AuthorizedOperation::SetWithdrawalFee
+const SET_WITHDRAWAL_FEE: u8 = 9;
+
+// AuthorizedOperation::RevokeAdminBadge
+const REVOKE_ADMIN_BADGE: u8 = 15;
+
+// Delay set on the operations under test
+const DELAY_HOURS: u32 = 1;
+
+// Two days, as in the blueprint
+const AUTHORIZATION_TIMEOUT: i64 = 172800;
+
+fn setup(operation: u8) -> TestFund {
+    let mut test_fund = TestFund::new();
+
+    admin_call!(test_fund, "set_operation_delay", operation, DELAY_HOURS)
+        .expect_commit_success();
+
+    test_fund
+}
+
+fn set_withdrawal_fee(test_fund: &mut TestFund) -> TransactionReceipt {
+    admin_call!(test_fund, "set_withdrawal_fee", 5u8)
+}
+
+fn withdrawal_fee_applied(test_fund: &mut TestFund) -> bool {
+    let (net_value, gross_value) = test_fund.query::<(Decimal, Decimal)>("fund_unit_value", manifest_args!());
+
+    net_value < gross_value
+}
+
+// Ids of the queued operations
+fn queued_operation_ids(test_fund: &mut TestFund) -> Vec<u64> {
+    test_fund.query::<Vec<(u64, u8, ScryptoValue, Hash, i64, i64)>>("queued_operations", manifest_args!())
+        .iter()
+        .map(|queued_operation| queued_operation.0)
+        .collect()
+}
+
+fn cancel_queued_operation(
+    test_fund: &mut TestFund,
+    admin_id: u64,
+    queued_operation_id: u64,
+) -> TransactionReceipt {
+    let fund_manager = test_fund.fund_manager;
+    let manifest = test_fund.admin_manifest(admin_id)
+        .call_method_with_name_lookup(fund_manager, "cancel_queued_operation", |lookup| (
+            lookup.proof("admin_proof"),
+            queued_operation_id,
+        ))
+        .build();
+
+    test_fund.execute(manifest)
+}
+
+#[test]
+fn queued_operation_is_performed_after_the_delay() {
+    let mut test_fund = setup(SET_WITHDRAWAL_FEE);
+
+    // The first invocation only queues the operation
+    set_withdrawal_fee(&mut test_fund)
+        .expect_commit_success();
+    assert!(!withdrawal_fee_applied(&mut test_fund));
+    assert_eq!(queued_operation_ids(&mut test_fund).len(), 1);
+
+    // Too early
+    test_fund.advance_time(3599);
+    set_withdrawal_fee(&mut test_fund)
+        .expect_commit_failure();
+    assert!(!withdrawal_fee_applied(&mut test_fund));
+
+    test_fund.advance_time(1);
+    set_withdrawal_fee(&mut test_fund)
+        .expect_commit_success();
+    assert!(withdrawal_fee_applied(&mut test_fund));
+    assert!(queued_operation_ids(&mut test_fund).is_empty());
+}
+
+#[test]
+fn queued_operation_expires() {
+    let mut test_fund = setup(SET_WITHDRAWAL_FEE);
+
+    set_withdrawal_fee(&mut test_fund)
+        .expect_commit_success();
+    let queued_operation_ids_before = queued_operation_ids(&mut test_fund);
+
+    test_fund.advance_time(3600 + AUTHORIZATION_TIMEOUT);
+    assert!(queued_operation_ids(&mut test_fund).is_empty());
+
+    // The expired operation is not performed: it is queued again instead
+    set_withdrawal_fee(&mut test_fund)
+        .expect_commit_success();
+    assert!(!withdrawal_fee_applied(&mut test_fund));
+    let queued_operation_ids_after = queued_operation_ids(&mut test_fund);
+    assert_eq!(queued_operation_ids_after.len(), 1);
+    assert_ne!(queued_operation_ids_after, queued_operation_ids_before);
+}
+
+#[test]
+fn any_admin_can_cancel_a_queued_operation() {
+    let mut test_fund = setup(SET_WITHDRAWAL_FEE);
+
+    set_withdrawal_fee(&mut test_fund)
+        .expect_commit_success();
+    let queued_operation_id = queued_operation_ids(&mut test_fund)[0];
+
+    cancel_queued_operation(&mut test_fund, 2, queued_operation_id)
+        .expect_commit_success();
+    assert!(queued_operation_ids(&mut test_fund).is_empty());
+
+    // After the delay the cancelled operation is queued again instead of being performed
+    test_fund.advance_time(3600);
+    set_withdrawal_fee(&mut test_fund)
+        .expect_commit_success();
+    assert!(!withdrawal_fee_applied(&mut test_fund));
+}
+
+#[test]
+fn revoked_admin_cannot_cancel_their_revocation() {
+    let mut test_fund = setup(REVOKE_ADMIN_BADGE);
+
+    // Admin 1 starts the revocation of admin badge 3
+    let account = test_fund.account;
+    let admin_badge = test_fund.admin_badge;
+    let admin_badge_vault = InternalAddress::new_or_panic(
+        test_fund.ledger.get_component_vaults(account, admin_badge)[0].0
+    );
+    admin_call!(test_fund, "revoke_admin_badge", 3u8, admin_badge_vault)
+        .expect_commit_success();
+    let queued_operation_id = queued_operation_ids(&mut test_fund)[0];
+
+    cancel_queued_operation(&mut test_fund, 3, queued_operation_id)
+        .expect_commit_failure();
+    assert_eq!(queued_operation_ids(&mut test_fund), vec![queued_operation_id]);
+
+    cancel_queued_operation(&mut test_fund, 2, queued_operation_id)
+        .expect_commit_success();
+    assert!(queued_operation_ids(&mut test_fund).is_empty());
+}
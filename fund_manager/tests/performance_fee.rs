@@ -0,0 +1,98 @@
+mod common;
+
+use common::*;
+use scrypto_test::prelude::*;
+
+// A fund with a 20% performance fee crystallized daily and paid to a new treasury account
+fn setup() -> (TestFund, ComponentAddress) {
+    let mut test_fund = TestFund::new();
+    let treasury = test_fund.new_account().address;
+
+    admin_call!(test_fund, "set_performance_fee", 20u8, 1u16, treasury)
+        .expect_commit_success();
+
+    (test_fund, treasury)
+}
+
+fn set_price(
+    test_fund: &mut TestFund,
+    price: Decimal,
+) {
+    let oracle = test_fund.oracle;
+    test_fund.execute(
+        ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(oracle, "set_price", manifest_args!(price))
+            .build()
+    )
+        .expect_commit_success();
+}
+
+fn high_water_mark(test_fund: &mut TestFund) -> Decimal {
+    test_fund.query::<(u8, Decimal, i64, i64)>("performance_fee_details", manifest_args!()).1
+}
+
+#[test]
+fn performance_fee_is_charged_on_the_gains_above_the_high_water_mark() {
+    let (mut test_fund, treasury) = setup();
+    let fund_unit = test_fund.fund_unit;
+    assert_eq!(high_water_mark(&mut test_fund), dec!(1));
+
+    // The fund doubles its value: the fee is 20% of the 1000 USD gain
+    set_price(&mut test_fund, dec!(2));
+    test_fund.advance_time(86400);
+    test_fund.update_defi_protocols_value()
+        .expect_commit_success();
+
+    // fee_value * total_supply / (total_value - fee_value) fund units are minted
+    let expected_amount = dec!(200) * dec!(1000) / (dec!(2000) - dec!(200));
+    assert_close(test_fund.balance(treasury, fund_unit), expected_amount);
+    assert_close(test_fund.total_supply(), dec!(1000) + expected_amount);
+
+    // The high-water mark is the gross value of a fund unit after the fee
+    assert_close(high_water_mark(&mut test_fund), dec!("1.8"));
+    assert_close(test_fund.fund_unit_gross_value(), dec!("1.8"));
+}
+
+#[test]
+fn performance_fee_is_not_charged_twice_on_the_same_gains() {
+    let (mut test_fund, treasury) = setup();
+    let fund_unit = test_fund.fund_unit;
+
+    set_price(&mut test_fund, dec!(2));
+    test_fund.advance_time(86400);
+    test_fund.update_defi_protocols_value()
+        .expect_commit_success();
+    let fee_fund_units = test_fund.balance(treasury, fund_unit);
+
+    // No new gains in the next period
+    test_fund.advance_time(86400);
+    test_fund.update_defi_protocols_value()
+        .expect_commit_success();
+    assert_eq!(test_fund.balance(treasury, fund_unit), fee_fund_units);
+
+    // A loss followed by a recovery up to the high-water mark doesn't pay any fee either
+    set_price(&mut test_fund, dec!("1.5"));
+    test_fund.advance_time(86400);
+    test_fund.update_defi_protocols_value()
+        .expect_commit_success();
+    set_price(&mut test_fund, dec!(2));
+    test_fund.advance_time(86400);
+    test_fund.update_defi_protocols_value()
+        .expect_commit_success();
+    assert_eq!(test_fund.balance(treasury, fund_unit), fee_fund_units);
+    assert_close(high_water_mark(&mut test_fund), dec!("1.8"));
+}
+
+#[test]
+fn performance_fee_waits_for_the_crystallization_period() {
+    let (mut test_fund, treasury) = setup();
+    let fund_unit = test_fund.fund_unit;
+
+    set_price(&mut test_fund, dec!(2));
+    test_fund.advance_time(86399);
+    test_fund.update_defi_protocols_value()
+        .expect_commit_success();
+    assert_eq!(test_fund.balance(treasury, fund_unit), Decimal::ZERO);
+    assert_eq!(high_water_mark(&mut test_fund), dec!(1));
+}
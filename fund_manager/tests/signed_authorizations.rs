@@ -0,0 +1,155 @@
+mod common;
+
+use common::*;
+use scrypto_test::prelude::*;
+
+// AuthorizedOperation::SetWithdrawalFee
+const SET_WITHDRAWAL_FEE: u8 = 9;
+
+// Require one cosigner for set_withdrawal_fee and register the signing key of admin 2
+fn setup() -> (TestFund, Ed25519PrivateKey) {
+    let mut test_fund = TestFund::new();
+
+    admin_call!(test_fund, "increase_min_authorizers", SET_WITHDRAWAL_FEE)
+        .expect_commit_success();
+
+    let signing_key = Ed25519PrivateKey::from_u64(2).unwrap();
+    let fund_manager = test_fund.fund_manager;
+    let manifest = test_fund.admin_manifest(2)
+        .call_method_with_name_lookup(fund_manager, "register_signing_key", |lookup| (
+            lookup.proof("admin_proof"),
+            signing_key.public_key(),
+        ))
+        .build();
+    test_fund.execute(manifest)
+        .expect_commit_success();
+
+    (test_fund, signing_key)
+}
+
+// Signature of admin 2 authorizing admin 1 to set the withdrawal fee to percentage
+fn sign_withdrawal_fee(
+    test_fund: &TestFund,
+    signing_key: &Ed25519PrivateKey,
+    percentage: u8,
+    deadline: i64,
+) -> Ed25519Signature {
+    let digest = hash(
+        scrypto_encode(&(
+            test_fund.fund_manager,
+            1u8,
+            SET_WITHDRAWAL_FEE,
+            arguments_hash(&(percentage,)),
+            deadline,
+        )).unwrap()
+    );
+
+    signing_key.sign(&digest)
+}
+
+// Manifest submitting the signatures and, optionally, setting the withdrawal fee in the same
+// transaction
+fn authorize_with_signatures_manifest(
+    test_fund: &TestFund,
+    percentage: u8,
+    deadline: i64,
+    signature: Ed25519Signature,
+    set_withdrawal_fee: bool,
+) -> TransactionManifestV1 {
+    let fund_manager = test_fund.fund_manager;
+    let mut manifest_builder = test_fund.admin_manifest(1)
+        .call_method_with_name_lookup(fund_manager, "authorize_with_signatures", |lookup| (
+            lookup.proof("admin_proof"),
+            SET_WITHDRAWAL_FEE,
+            arguments_hash(&(percentage,)),
+            deadline,
+            vec![(2u8, signature)],
+        ));
+
+    if set_withdrawal_fee {
+        manifest_builder = manifest_builder
+            .create_proof_from_account_of_non_fungibles(
+                test_fund.account,
+                test_fund.admin_badge,
+                [NonFungibleLocalId::integer(1)],
+            )
+            .pop_from_auth_zone("admin_proof2")
+            .call_method_with_name_lookup(fund_manager, "set_withdrawal_fee", |lookup| (
+                lookup.proof("admin_proof2"),
+                percentage,
+            ));
+    }
+
+    manifest_builder.build()
+}
+
+fn withdrawal_fee_applied(test_fund: &mut TestFund) -> bool {
+    let (net_value, gross_value) = test_fund.query::<(Decimal, Decimal)>("fund_unit_value", manifest_args!());
+
+    net_value < gross_value
+}
+
+#[test]
+fn signed_authorization_is_consumed_in_the_same_transaction() {
+    let (mut test_fund, signing_key) = setup();
+
+    // Without the signature the operation is not authorized
+    admin_call!(test_fund, "set_withdrawal_fee", 5u8)
+        .expect_commit_failure();
+
+    let deadline = test_fund.now + 3600;
+    let signature = sign_withdrawal_fee(&test_fund, &signing_key, 5, deadline);
+    let manifest = authorize_with_signatures_manifest(&test_fund, 5, deadline, signature, true);
+    test_fund.execute(manifest)
+        .expect_commit_success();
+
+    assert!(withdrawal_fee_applied(&mut test_fund));
+}
+
+#[test]
+fn signed_authorization_does_not_outlive_the_transaction() {
+    let (mut test_fund, signing_key) = setup();
+
+    let deadline = test_fund.now + 3600;
+    let signature = sign_withdrawal_fee(&test_fund, &signing_key, 5, deadline);
+    let manifest = authorize_with_signatures_manifest(&test_fund, 5, deadline, signature, false);
+    test_fund.execute(manifest)
+        .expect_commit_success();
+
+    // The signatures were submitted in another transaction
+    admin_call!(test_fund, "set_withdrawal_fee", 5u8)
+        .expect_commit_failure();
+    assert!(!withdrawal_fee_applied(&mut test_fund));
+
+    // Nor they can be submitted again
+    let manifest = authorize_with_signatures_manifest(&test_fund, 5, deadline, signature, true);
+    test_fund.execute(manifest)
+        .expect_commit_failure();
+    assert!(!withdrawal_fee_applied(&mut test_fund));
+}
+
+#[test]
+fn signed_authorization_is_refused_after_the_deadline() {
+    let (mut test_fund, signing_key) = setup();
+
+    let deadline = test_fund.now + 60;
+    let signature = sign_withdrawal_fee(&test_fund, &signing_key, 5, deadline);
+    test_fund.advance_time(61);
+
+    let manifest = authorize_with_signatures_manifest(&test_fund, 5, deadline, signature, true);
+    test_fund.execute(manifest)
+        .expect_commit_failure();
+    assert!(!withdrawal_fee_applied(&mut test_fund));
+}
+
+#[test]
+fn signed_authorization_is_only_valid_for_the_signed_arguments() {
+    let (mut test_fund, signing_key) = setup();
+
+    let deadline = test_fund.now + 3600;
+    let signature = sign_withdrawal_fee(&test_fund, &signing_key, 5, deadline);
+    let manifest = authorize_with_signatures_manifest(&test_fund, 10, deadline, signature, true);
+    test_fund.execute(manifest)
+        .expect_commit_failure();
+    assert!(!withdrawal_fee_applied(&mut test_fund));
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::*;
+use scrypto_test::prelude::*;
+
+#[test]
+fn first_subscription_is_minted_one_to_one() {
+    let mut test_fund = TestFund::with_initial_supply(Decimal::ZERO);
+    assert_eq!(test_fund.total_supply(), Decimal::ZERO);
+    assert_eq!(test_fund.fund_unit_gross_value(), dec!(1));
+
+    let account = test_fund.account;
+    let fund_unit = test_fund.fund_unit;
+    test_fund.subscribe(dec!(500))
+        .expect_commit_success();
+
+    assert_eq!(test_fund.balance(account, fund_unit), dec!(500));
+    assert_eq!(test_fund.total_supply(), dec!(500));
+    assert_eq!(test_fund.fund_unit_gross_value(), dec!(1));
+}
+
+#[test]
+fn entry_fee_stays_in_the_fund() {
+    let mut test_fund = TestFund::new();
+    admin_call!(test_fund, "set_entry_fee", 5u8)
+        .expect_commit_success();
+
+    let account = test_fund.account;
+    let fund_unit = test_fund.fund_unit;
+    let fund_units = test_fund.balance(account, fund_unit);
+    test_fund.subscribe(dec!(100))
+        .expect_commit_success();
+
+    // The fee is not minted, so it raises the value of every fund unit
+    assert_eq!(test_fund.balance(account, fund_unit) - fund_units, dec!(95));
+    assert_eq!(test_fund.total_supply(), dec!(1095));
+    assert_close(test_fund.fund_unit_gross_value(), dec!(1100) / dec!(1095));
+}
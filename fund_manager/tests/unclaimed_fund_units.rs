@@ -0,0 +1,85 @@
+mod common;
+
+use common::*;
+use scrypto_test::prelude::*;
+
+#[test]
+fn reclaim_only_touches_the_distribution_account_locker() {
+    let mut test_fund = TestFund::new();
+    let fund_unit_amount = test_fund.new_fund_units_to_distribute(dec!(100));
+
+    // The only staker refuses deposits so its fund units end in the distribution AccountLocker
+    let staker = test_fund.new_account();
+    test_fund.reject_deposits(&staker);
+    let stakers = vec![(staker.address, dec!(1))];
+    test_fund.commit_distribution_plan(&stakers)
+        .expect_commit_success();
+    test_fund.fund_units_distribution(&stakers, false)
+        .expect_commit_success();
+    assert_eq!(test_fund.balance(staker.address, test_fund.fund_unit), Decimal::ZERO);
+
+    let account_lockers = test_fund.account_lockers.clone();
+    assert_eq!(account_lockers.len(), 2);
+    let (distribution_account_locker, account_locker) = match test_fund.locker_amount(account_lockers[0], staker.address) > Decimal::ZERO {
+        true => (account_lockers[0], account_lockers[1]),
+        false => (account_lockers[1], account_lockers[0]),
+    };
+    assert_eq!(test_fund.locker_amount(distribution_account_locker, staker.address), fund_unit_amount);
+
+    // An admin stores some fund units for the same account in the other AccountLocker
+    let account = test_fund.account;
+    let admin_badge = test_fund.admin_badge;
+    let fund_unit = test_fund.fund_unit;
+    test_fund.execute(
+        ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_non_fungibles(account, admin_badge, [NonFungibleLocalId::integer(1)])
+            .withdraw_from_account(account, fund_unit, dec!(10))
+            .take_all_from_worktop(fund_unit, "fund_units")
+            .call_method_with_name_lookup(account_locker, "store", |lookup| (
+                staker.address,
+                lookup.bucket("fund_units"),
+                false,
+            ))
+            .build()
+    )
+        .expect_commit_success();
+
+    // The staker claims part of its fund units
+    test_fund.ledger.execute_manifest(
+        ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(
+                distribution_account_locker,
+                "claim",
+                manifest_args!(staker.address, fund_unit, dec!(1)),
+            )
+            .call_method(staker.address, "deposit_batch", manifest_args!(ManifestExpression::EntireWorktop))
+            .build(),
+        vec![NonFungibleGlobalId::from_public_key(&staker.public_key)],
+    )
+        .expect_commit_success();
+    assert_eq!(test_fund.balance(staker.address, fund_unit), dec!(1));
+
+    // Nothing is expired yet: the fund units left in the distribution AccountLocker are found
+    // there and attributed to the distribution plan
+    let total_supply = test_fund.total_supply();
+    let accounts = vec![staker.address];
+    test_fund.bot_call("reclaim_unclaimed_fund_units", manifest_args!(accounts, false))
+        .expect_commit_success();
+    assert_eq!(test_fund.total_supply(), total_supply);
+    let unclaimed_fund_units = test_fund.query::<IndexMap<u64, (i64, Decimal)>>("unclaimed_fund_units", manifest_args!());
+    assert_eq!(unclaimed_fund_units.get(&1).unwrap().1, fund_unit_amount - dec!(1));
+
+    admin_call!(test_fund, "set_unclaimed_fund_units_expiry", 1u16)
+        .expect_commit_success();
+    test_fund.advance_time(86401);
+
+    test_fund.bot_call("reclaim_unclaimed_fund_units", manifest_args!(accounts, false))
+        .expect_commit_success();
+
+    // Only the fund units still in the distribution AccountLocker are burnt
+    assert_eq!(test_fund.total_supply(), total_supply - fund_unit_amount + dec!(1));
+    assert_eq!(test_fund.locker_amount(distribution_account_locker, staker.address), Decimal::ZERO);
+    assert_eq!(test_fund.locker_amount(account_locker, staker.address), dec!(10));
+}
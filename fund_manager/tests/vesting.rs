@@ -0,0 +1,64 @@
+mod common;
+
+use common::*;
+use scrypto_test::prelude::*;
+
+// Claim the fund units released by the first vesting receipt
+fn claim_vested(
+    test_fund: &mut TestFund,
+    staker: &TestAccount,
+) -> TransactionReceipt {
+    test_fund.ledger.execute_manifest(
+        ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_non_fungibles(
+                staker.address,
+                test_fund.vesting_receipt,
+                [NonFungibleLocalId::integer(1)],
+            )
+            .pop_from_auth_zone("vesting_receipts_proof")
+            .call_method_with_name_lookup(test_fund.fund_manager, "claim_vested", |lookup| (
+                lookup.proof("vesting_receipts_proof"),
+            ))
+            .call_method(staker.address, "deposit_batch", manifest_args!(ManifestExpression::EntireWorktop))
+            .build(),
+        vec![NonFungibleGlobalId::from_public_key(&staker.public_key)],
+    )
+}
+
+#[test]
+fn vested_fund_units_are_released_linearly() {
+    let mut test_fund = TestFund::new();
+    admin_call!(test_fund, "set_vesting_period", 10u16)
+        .expect_commit_success();
+
+    let fund_unit_amount = test_fund.new_fund_units_to_distribute(dec!(100));
+    let staker = test_fund.new_account();
+    let stakers = vec![(staker.address, dec!(1))];
+    test_fund.commit_distribution_plan(&stakers)
+        .expect_commit_success();
+    test_fund.fund_units_distribution(&stakers, false)
+        .expect_commit_success();
+
+    // The staker gets a vesting receipt instead of the fund units
+    let fund_unit = test_fund.fund_unit;
+    let vesting_receipt = test_fund.vesting_receipt;
+    assert_eq!(test_fund.balance(staker.address, vesting_receipt), dec!(1));
+    assert_eq!(test_fund.balance(staker.address, fund_unit), Decimal::ZERO);
+
+    // Half of the fund units are released after half of the vesting period
+    test_fund.advance_time(5 * 86400);
+    claim_vested(&mut test_fund, &staker)
+        .expect_commit_success();
+    assert_close(test_fund.balance(staker.address, fund_unit), fund_unit_amount / 2);
+    let (claimable_amount, locked_amount) = test_fund.query::<(Decimal, Decimal)>("vested_amount", manifest_args!(1u64));
+    assert_eq!(claimable_amount, Decimal::ZERO);
+    assert_close(locked_amount, fund_unit_amount / 2);
+
+    // The liquidation releases everything
+    admin_call!(test_fund, "start_liquidation")
+        .expect_commit_success();
+    claim_vested(&mut test_fund, &staker)
+        .expect_commit_success();
+    assert_eq!(test_fund.balance(staker.address, fund_unit), fund_unit_amount);
+}